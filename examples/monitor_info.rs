@@ -1,10 +1,12 @@
 use std::sync::mpsc;
 
-use wlx_monitors::{WlMonitorEvent, WlMonitorManager};
+use wlx_monitors::{
+    WlMonitorChanges, WlMonitorEvent, WlMonitorManager, action_channel,
+};
 
 fn main() {
     let (event_tx, event_rx) = mpsc::sync_channel(16);
-    let (_action_tx, action_rx) = mpsc::sync_channel(16);
+    let (_action_tx, action_rx) = action_channel(16);
 
     let (state, event_queue) =
         WlMonitorManager::new_connection(event_tx, action_rx)
@@ -21,6 +23,14 @@ fn main() {
                 for monitor in &monitors {
                     println!("  {} ({})", monitor.name, monitor.description);
                     println!("    enabled: {}", monitor.enabled);
+                    let logical = monitor.logical_size();
+                    println!(
+                        "    resolution: {}x{} (logical {}x{})",
+                        monitor.resolution.width,
+                        monitor.resolution.height,
+                        logical.width,
+                        logical.height
+                    );
                     println!(
                         "    position: ({}, {})",
                         monitor.position.x, monitor.position.y
@@ -36,27 +46,74 @@ fn main() {
                             flags.push_str(" [CURRENT]");
                         }
                         println!(
-                            "      {}x{} @ {}Hz{}",
+                            "      {}x{} @ {:.3}Hz{}",
                             mode.resolution.width,
                             mode.resolution.height,
-                            mode.refresh_rate,
+                            mode.refresh_hz(),
                             flags,
                         );
                     }
                     println!();
                 }
             }
-            WlMonitorEvent::Changed(monitor) => {
-                println!("=== changed: {} ===", monitor.name);
+            WlMonitorEvent::Changed {
+                monitor,
+                changes,
+                previous,
+                serial,
+            } => {
+                println!(
+                    "=== changed: {} ({:?}, serial {}) ===",
+                    monitor.name, changes, serial
+                );
                 println!("    enabled: {}", monitor.enabled);
+                if changes.contains(WlMonitorChanges::MODE) {
+                    println!(
+                        "    resolution: {}x{} -> {}x{}",
+                        previous.resolution.width,
+                        previous.resolution.height,
+                        monitor.resolution.width,
+                        monitor.resolution.height,
+                    );
+                }
+                println!();
+            }
+            WlMonitorEvent::Added(monitor) => {
+                println!(
+                    "=== added: {} ({}) ===",
+                    monitor.name, monitor.description
+                );
+                println!(
+                    "    resolution: {}x{}",
+                    monitor.resolution.width, monitor.resolution.height
+                );
                 println!();
             }
             WlMonitorEvent::Removed { name, .. } => {
                 println!("=== removed: {} ===", name);
             }
-            WlMonitorEvent::ActionFailed { action, reason } => {
-                eprintln!("Action failed: {:?}", action);
-                eprintln!("Reason: {}", reason);
+            WlMonitorEvent::ActionSucceeded { id } => {
+                println!("Action {} succeeded", id);
+            }
+            WlMonitorEvent::ActionFailed { id, action, error } => {
+                eprintln!("Action {} failed: {:?}", id, action);
+                eprintln!("Error: {}", error);
+            }
+            WlMonitorEvent::TestResult {
+                id,
+                action,
+                succeeded,
+            } => {
+                println!("Test {} {:?}: succeeded={}", id, action, succeeded);
+            }
+            WlMonitorEvent::OutputManagerRemoved => {
+                eprintln!("=== output manager removed ===");
+            }
+            WlMonitorEvent::Disconnected => {
+                eprintln!("=== disconnected ===");
+            }
+            WlMonitorEvent::Reconnected => {
+                println!("=== reconnected ===");
             }
         }
     }
@@ -23,9 +23,17 @@ mod state;
 mod wl_monitor;
 
 pub use state::{
-    ActionKind, WlMonitorAction, WlMonitorEvent, WlMonitorManager,
-    WlMonitorManagerError,
+    ActionKind, WlActionId, WlActionReceiver, WlActionSender, WlEventFilter,
+    WlEventKinds, WlEventSubscriber, WlHeadChange, WlMonitorAction,
+    WlMonitorEvent, WlMonitorManager, WlMonitorManagerError,
+    WlMonitorStateHandle, WlReconnectPolicy, WlTransaction, action_channel,
+    snapshot,
 };
+#[cfg(feature = "async")]
+pub use state::{AsyncWlMonitorManager, WlMonitorEventStream};
+#[cfg(feature = "calloop")]
+pub use state::{WlEventSourceSender, WlMonitorEventSource};
 pub use wl_monitor::{
-    WlMonitor, WlMonitorMode, WlPosition, WlResolution, WlTransform,
+    WlAdaptiveSync, WlMonitor, WlMonitorChanges, WlMonitorMode, WlPosition,
+    WlRefreshRate, WlResolution, WlTransform,
 };
@@ -1,34 +1,126 @@
-use wayland_client::{EventQueue, Proxy, QueueHandle, backend::ObjectId};
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use wayland_client::{Proxy, QueueHandle, backend::ObjectId};
 use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1,
     zwlr_output_configuration_v1::ZwlrOutputConfigurationV1,
+    zwlr_output_manager_v1::ZwlrOutputManagerV1,
 };
 
-use crate::wl_monitor::{WlMonitor, WlTransform};
+use crate::wl_monitor::{
+    WlAdaptiveSync, WlMonitor, WlMonitorChanges, WlRefreshRate, WlTransform,
+};
 
-use super::{WlMonitorManager, WlMonitorManagerError};
+use super::{
+    CancelledAction, ConfigResult, PendingConfig, WlMonitorManager,
+    WlMonitorManagerError, WlTransaction,
+};
 
 /// The kind of action that failed
-#[derive(Debug, Clone, PartialEq, Eq)]
+///
+/// `ActionFailed` carries the kind of the action that was sent, whether it
+/// failed validation or was rejected by the compositor. A
+/// [`WlMonitorAction::Test`] reports the kind of the action it wraps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ActionKind {
     Toggle,
-    ConfigApply,
     SwitchMode,
+    SetCustomMode,
     SetScale,
     SetTransform,
     SetPosition,
+    SetAdaptiveSync,
+    Apply,
+    Shutdown,
+}
+
+/// Identifier correlating an action with the events it produces
+///
+/// IDs are either generated with [`WlActionId::next`] (as
+/// [`WlActionSender::send`] does) or supplied by the caller with
+/// [`WlActionId::new`]. Do not mix caller-supplied IDs with generated ones
+/// if they need to be unique.
+///
+/// [`WlActionSender::send`]: super::WlActionSender::send
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WlActionId(pub u64);
+
+impl WlActionId {
+    /// Wrap a caller-supplied ID
+    pub fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// Generate a new process-wide unique ID
+    pub fn next() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for WlActionId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
 }
 
 /// Events emitted by the Wayland monitor manager
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum WlMonitorEvent {
     /// Sent once when the initial state is received, containing all connected monitors
     InitialState(Vec<WlMonitor>),
     /// Sent when a monitor's properties have changed
-    Changed(Box<WlMonitor>),
+    ///
+    /// Changes are collected until the compositor's `done` event, so
+    /// `monitor` always reflects a complete update.
+    Changed {
+        /// The monitor's new state
+        monitor: Box<WlMonitor>,
+        /// Which properties changed
+        changes: WlMonitorChanges,
+        /// The monitor's state before the change
+        previous: Box<WlMonitor>,
+        /// Serial of the `done` event that completed the update
+        serial: u32,
+    },
+    /// Sent when a monitor is connected after the initial state, once the
+    /// compositor sent all of its properties
+    Added(Box<WlMonitor>),
     /// Sent when a monitor is disconnected
     Removed { id: ObjectId, name: String },
+    /// Sent when an action has been applied by the compositor
+    ActionSucceeded { id: WlActionId },
     /// Sent when an action fails (e.g., invalid mode specified)
-    ActionFailed { action: ActionKind, reason: String },
+    ActionFailed {
+        id: WlActionId,
+        action: ActionKind,
+        error: WlMonitorManagerError,
+    },
+    /// Sent with the compositor's answer to a [`WlMonitorAction::Test`]
+    TestResult {
+        id: WlActionId,
+        action: ActionKind,
+        succeeded: bool,
+    },
+    /// Sent when the compositor withdraws the output manager global
+    ///
+    /// Actions waiting for the compositor and every later action fail with
+    /// [`WlMonitorManagerError::ProtocolUnsupported`], and the known
+    /// monitors are forgotten.
+    OutputManagerRemoved,
+    /// Sent by [`WlMonitorManager::run_with_reconnect`] when the connection
+    /// to the compositor is lost; every known monitor is forgotten
+    ///
+    /// [`WlMonitorManager::run_with_reconnect`]: super::WlMonitorManager::run_with_reconnect
+    Disconnected,
+    /// Sent by [`WlMonitorManager::run_with_reconnect`] once it reconnected,
+    /// followed by a fresh [`WlMonitorEvent::InitialState`]
+    ///
+    /// [`WlMonitorManager::run_with_reconnect`]: super::WlMonitorManager::run_with_reconnect
+    Reconnected,
 }
 
 /// Actions that can be sent to the monitor manager to control monitors
@@ -38,7 +130,7 @@ pub enum WlMonitorAction {
         /// Name of the monitor to toggle (e.g., "DP-1")
         name: String,
         /// Optional custom mode: (width, height, refresh_rate)
-        mode: Option<(i32, i32, i32)>,
+        mode: Option<(i32, i32, WlRefreshRate)>,
         /// Optional position to set when enabling: (x, y)
         position: Option<(i32, i32)>,
     },
@@ -50,8 +142,20 @@ pub enum WlMonitorAction {
         width: i32,
         /// Desired height in pixels
         height: i32,
-        /// Desired refresh rate in Hz
-        refresh_rate: i32,
+        /// Desired refresh rate (whole Hz, exact mHz, nearest or within a
+        /// tolerance)
+        refresh_rate: WlRefreshRate,
+    },
+    /// Switch a monitor to a mode that is not advertised by the compositor
+    SetCustomMode {
+        /// Name of the monitor to configure
+        name: String,
+        /// Desired width in pixels
+        width: i32,
+        /// Desired height in pixels
+        height: i32,
+        /// Desired refresh rate in mHz (0 lets the compositor pick one)
+        refresh_mhz: i32,
     },
     /// Set a monitor's scale factor
     SetScale {
@@ -76,76 +180,314 @@ pub enum WlMonitorAction {
         /// Y coordinate in the global coordinate space
         y: i32,
     },
+    /// Enable or disable adaptive sync (VRR) on a monitor
+    SetAdaptiveSync {
+        /// Name of the monitor to configure (e.g., "DP-1")
+        name: String,
+        /// Whether adaptive sync should be enabled
+        enabled: bool,
+    },
+    /// Apply changes to any number of monitors in a single configuration
+    ///
+    /// The compositor either accepts the whole transaction or rejects it,
+    /// so intermediate layouts are never applied.
+    Apply(WlTransaction),
+    /// Ask the compositor whether the wrapped action would succeed, without
+    /// applying it
+    ///
+    /// The answer is reported through [`WlMonitorEvent::TestResult`].
+    Test(Box<WlMonitorAction>),
+    /// Stop the manager: release the output manager, heads and modes and
+    /// return `Ok(())` from [`WlMonitorManager::run`]
+    ///
+    /// Actions still in flight or queued behind it fail with
+    /// [`WlMonitorManagerError::ConfigurationAborted`].
+    Shutdown,
+}
+
+impl WlMonitorAction {
+    /// The kind of this action, as reported in events
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Self::Toggle { .. } => ActionKind::Toggle,
+            Self::SwitchMode { .. } => ActionKind::SwitchMode,
+            Self::SetCustomMode { .. } => ActionKind::SetCustomMode,
+            Self::SetScale { .. } => ActionKind::SetScale,
+            Self::SetTransform { .. } => ActionKind::SetTransform,
+            Self::SetPosition { .. } => ActionKind::SetPosition,
+            Self::SetAdaptiveSync { .. } => ActionKind::SetAdaptiveSync,
+            Self::Apply(_) => ActionKind::Apply,
+            Self::Test(inner) => inner.kind(),
+            Self::Shutdown => ActionKind::Shutdown,
+        }
+    }
+
+    /// Wrap this action in [`WlMonitorAction::Test`]
+    pub fn test(self) -> Self {
+        Self::Test(Box::new(self))
+    }
 }
 
 impl WlMonitorManager {
+    /// Build and send the configuration for `action` without waiting for
+    /// the compositor's answer, which is handled by [`Self::finish_config`]
+    ///
+    /// Every action ends with exactly one `ActionSucceeded`, `ActionFailed`
+    /// or `TestResult` event carrying `id`.
     pub(super) fn handle_action(
         &mut self,
+        id: WlActionId,
         action: WlMonitorAction,
-        eq: &mut EventQueue<Self>,
-    ) -> Result<(), WlMonitorManagerError> {
-        let serial = self.serial.ok_or_else(|| {
-            WlMonitorManagerError::EventQueueError("no serial available".into())
-        })?;
-        let manager = self.zwlr_manager.as_ref().ok_or_else(|| {
-            WlMonitorManagerError::EventQueueError(
-                "no manager available".into(),
-            )
-        })?;
-
-        let qh = eq.handle();
-        let config = manager.create_configuration(serial, &qh, ());
-
-        match action {
+        qh: &QueueHandle<Self>,
+    ) {
+        self.send_action(id, action, 0, qh);
+    }
+
+    /// Send `action` as its `attempt`-th retry
+    fn send_action(
+        &mut self,
+        id: WlActionId,
+        action: WlMonitorAction,
+        attempt: u32,
+        qh: &QueueHandle<Self>,
+    ) {
+        let Some(serial) = self.serial else {
+            self.emitter.send(WlMonitorEvent::ActionFailed {
+                id,
+                action: action.kind(),
+                error: WlMonitorManagerError::NotReady,
+            });
+            return;
+        };
+        let Some(manager) = self.zwlr_manager.as_ref() else {
+            self.emitter.send(WlMonitorEvent::ActionFailed {
+                id,
+                action: action.kind(),
+                error: WlMonitorManagerError::ProtocolUnsupported(
+                    ZwlrOutputManagerV1::interface().name,
+                ),
+            });
+            return;
+        };
+
+        let mut test_only = false;
+        let mut action = action;
+        while let WlMonitorAction::Test(inner) = action {
+            test_only = true;
+            action = *inner;
+        }
+        let kind = action.kind();
+
+        let config = manager.create_configuration(serial, qh, ());
+
+        let configured = match action {
             WlMonitorAction::Toggle {
                 ref name,
                 mode,
                 position,
-            } => {
-                self.configure_toggle(&config, name, &qh, mode, position);
-            }
+            } => self.configure_toggle(&config, name, qh, mode, position),
             WlMonitorAction::SwitchMode {
                 ref name,
                 width,
                 height,
                 refresh_rate,
-            } => {
-                self.configure_switch_mode(
-                    &config,
-                    name,
-                    width,
-                    height,
-                    refresh_rate,
-                    &qh,
-                );
-            }
+            } => self.configure_switch_mode(
+                &config,
+                name,
+                width,
+                height,
+                refresh_rate,
+                qh,
+            ),
+            WlMonitorAction::SetCustomMode {
+                ref name,
+                width,
+                height,
+                refresh_mhz,
+            } => self.configure_set_custom_mode(
+                &config,
+                name,
+                width,
+                height,
+                refresh_mhz,
+                qh,
+            ),
             WlMonitorAction::SetScale { ref name, scale } => {
-                self.configure_set_scale(&config, name, scale, &qh);
+                self.configure_set_scale(&config, name, scale, qh)
             }
             WlMonitorAction::SetTransform {
                 ref name,
                 transform,
-            } => {
-                self.configure_set_transform(&config, name, transform, &qh);
-            }
+            } => self.configure_set_transform(&config, name, transform, qh),
             WlMonitorAction::SetPosition { ref name, x, y } => {
-                self.configure_set_position(&config, name, x, y, &qh);
+                self.configure_set_position(&config, name, x, y, qh)
+            }
+            WlMonitorAction::SetAdaptiveSync { ref name, enabled } => {
+                self.configure_set_adaptive_sync(&config, name, enabled, qh)
             }
+            WlMonitorAction::Apply(ref transaction) => {
+                self.configure_apply(&config, transaction, qh)
+            }
+            WlMonitorAction::Shutdown => {
+                Err(WlMonitorManagerError::NotTestable(kind))
+            }
+            WlMonitorAction::Test(_) => unreachable!(),
+        };
+
+        // Invalid actions never reach the compositor
+        if let Err(error) = configured {
+            self.emitter.send(WlMonitorEvent::ActionFailed {
+                id,
+                action: kind,
+                error,
+            });
+            config.destroy();
+            return;
         }
 
-        config.apply();
-        match self.wait_for_result(eq) {
-            Ok(()) => {}
-            Err(e) => {
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::ConfigApply,
-                    reason: format!("{:?}", e),
-                });
-            }
+        if test_only {
+            config.test();
+        } else {
+            config.apply();
         }
+        self.pending.insert(
+            config.id(),
+            PendingConfig {
+                id,
+                config,
+                action,
+                test_only,
+                deadline: Instant::now() + self.action_timeout,
+                serial,
+                attempt,
+            },
+        );
+    }
+
+    /// Report the compositor's answer (or the lack of one) to a pending
+    /// configuration and destroy it
+    pub(super) fn finish_config(
+        &mut self,
+        config_id: &ObjectId,
+        result: ConfigResult,
+    ) {
+        let Some(pending) = self.pending.remove(config_id) else {
+            return;
+        };
+        let PendingConfig {
+            id,
+            config,
+            action,
+            test_only,
+            serial,
+            attempt,
+            ..
+        } = pending;
         config.destroy();
 
-        Ok(())
+        if result == ConfigResult::Cancelled && attempt < self.max_retries {
+            let action = if test_only { action.test() } else { action };
+            self.cancelled.push(CancelledAction {
+                id,
+                action,
+                serial,
+                attempt: attempt + 1,
+            });
+            return;
+        }
+
+        let kind = action.kind();
+        let event = match result {
+            ConfigResult::Succeeded if test_only => {
+                WlMonitorEvent::TestResult {
+                    id,
+                    action: kind,
+                    succeeded: true,
+                }
+            }
+            ConfigResult::Succeeded => WlMonitorEvent::ActionSucceeded { id },
+            ConfigResult::Failed if test_only => WlMonitorEvent::TestResult {
+                id,
+                action: kind,
+                succeeded: false,
+            },
+            ConfigResult::Failed => match action {
+                WlMonitorAction::SetCustomMode {
+                    ref name,
+                    width,
+                    height,
+                    refresh_mhz,
+                } => WlMonitorEvent::ActionFailed {
+                    id,
+                    action: kind,
+                    error: WlMonitorManagerError::CustomModeRejected {
+                        name: name.clone(),
+                        width,
+                        height,
+                        refresh_mhz,
+                    },
+                },
+                _ => WlMonitorEvent::ActionFailed {
+                    id,
+                    action: kind,
+                    error: WlMonitorManagerError::ConfigurationFailed,
+                },
+            },
+            ConfigResult::Cancelled => WlMonitorEvent::ActionFailed {
+                id,
+                action: kind,
+                error: WlMonitorManagerError::ConfigurationCancelled,
+            },
+            ConfigResult::TimedOut => WlMonitorEvent::ActionFailed {
+                id,
+                action: kind,
+                error: WlMonitorManagerError::ConfigurationTimedOut(
+                    self.action_timeout,
+                ),
+            },
+            ConfigResult::Aborted => WlMonitorEvent::ActionFailed {
+                id,
+                action: kind,
+                error: WlMonitorManagerError::ConfigurationAborted,
+            },
+            ConfigResult::Error(error) => WlMonitorEvent::ActionFailed {
+                id,
+                action: kind,
+                error,
+            },
+        };
+        self.emitter.send(event);
+    }
+
+    /// Rebuild cancelled actions once the compositor sent a newer serial
+    pub(super) fn retry_cancelled(&mut self, qh: &QueueHandle<Self>) {
+        let serial = self.serial;
+        let (ready, waiting) = std::mem::take(&mut self.cancelled)
+            .into_iter()
+            .partition(|cancelled| Some(cancelled.serial) != serial);
+        self.cancelled = waiting;
+        for cancelled in ready {
+            self.send_action(
+                cancelled.id,
+                cancelled.action,
+                cancelled.attempt,
+                qh,
+            );
+        }
+    }
+
+    /// Fail every configuration the compositor has not answered in time
+    pub(super) fn expire_pending(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<ObjectId> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in expired {
+            self.finish_config(&id, ConfigResult::TimedOut);
+        }
     }
 
     fn configure_toggle(
@@ -153,15 +495,10 @@ impl WlMonitorManager {
         config: &ZwlrOutputConfigurationV1,
         name: &str,
         qh: &QueueHandle<Self>,
-        mode: Option<(i32, i32, i32)>,
+        mode: Option<(i32, i32, WlRefreshRate)>,
         position: Option<(i32, i32)>,
-    ) {
-        let target_enabled = self
-            .monitors
-            .values()
-            .find(|m| m.name == name)
-            .map(|m| m.enabled)
-            .unwrap_or(false);
+    ) -> Result<(), WlMonitorManagerError> {
+        let target_enabled = self.find_monitor(name)?.enabled;
 
         // Save last_mode before the main loop so the mutable borrow is scoped separately
         if target_enabled {
@@ -187,11 +524,7 @@ impl WlMonitorManager {
 
             let resolved_mode =
                 if let Some((width, height, refresh_rate)) = mode {
-                    monitor.modes.iter().find(|m| {
-                        m.resolution.width == width
-                            && m.resolution.height == height
-                            && m.refresh_rate == refresh_rate
-                    })
+                    monitor.find_mode(width, height, refresh_rate)
                 } else if let Some(last_mode) = &monitor.last_mode {
                     monitor.modes.iter().find(|m| m.mode_id == *last_mode)
                 } else {
@@ -213,16 +546,15 @@ impl WlMonitorManager {
                 head.set_position(pos_x, pos_y);
                 head.set_transform(monitor.transform.to_wayland());
                 head.set_scale(monitor.scale);
+                Self::set_adaptive_sync(&head, monitor.adaptive_sync);
             } else {
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::Toggle,
-                    reason: format!(
-                        "No valid mode available for monitor '{}'",
-                        name
-                    ),
-                });
+                return Err(WlMonitorManagerError::NoValidMode(
+                    name.to_string(),
+                ));
             }
         }
+
+        Ok(())
     }
 
     fn configure_switch_mode(
@@ -231,58 +563,85 @@ impl WlMonitorManager {
         name: &str,
         width: i32,
         height: i32,
-        refresh_rate: i32,
+        refresh_rate: WlRefreshRate,
         qh: &QueueHandle<Self>,
-    ) {
+    ) -> Result<(), WlMonitorManagerError> {
+        self.find_monitor(name)?;
+
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
                 continue;
             }
 
-            if let Some(mode) = monitor.modes.iter().find(|m| {
-                m.resolution.width == width
-                    && m.resolution.height == height
-                    && m.refresh_rate == refresh_rate
-            }) {
+            if let Some(mode) = monitor.find_mode(width, height, refresh_rate) {
                 let config_head = config.enable_head(&monitor.head, qh, ());
                 config_head.set_mode(&mode.proxy);
                 config_head
                     .set_position(monitor.position.x, monitor.position.y);
                 config_head.set_transform(monitor.transform.to_wayland());
                 config_head.set_scale(monitor.scale);
+                Self::set_adaptive_sync(&config_head, monitor.adaptive_sync);
             } else {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SwitchMode,
-                    reason: format!(
-                        "No matching mode {}x{}@{}Hz for monitor '{}'",
-                        width, height, refresh_rate, name
-                    ),
+                return Err(WlMonitorManagerError::NoMatchingMode {
+                    name: name.to_string(),
+                    width,
+                    height,
+                    refresh_rate,
                 });
             }
         }
+
+        Ok(())
     }
 
-    fn configure_set_scale(
+    fn configure_set_custom_mode(
         &self,
         config: &ZwlrOutputConfigurationV1,
         name: &str,
-        scale: f64,
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
         qh: &QueueHandle<Self>,
-    ) {
-        if !scale.is_finite() || scale <= 0.0 {
-            let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                action: ActionKind::SetScale,
-                reason: format!(
-                    "Invalid scale value '{}': must be finite and > 0",
-                    scale
-                ),
+    ) -> Result<(), WlMonitorManagerError> {
+        self.find_monitor(name)?;
+
+        if width <= 0 || height <= 0 || refresh_mhz < 0 {
+            return Err(WlMonitorManagerError::InvalidCustomMode {
+                width,
+                height,
+                refresh_mhz,
             });
-            for monitor in self.monitors.values() {
+        }
+
+        for monitor in self.monitors.values() {
+            if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
+                continue;
             }
-            return;
+
+            let config_head = config.enable_head(&monitor.head, qh, ());
+            config_head.set_custom_mode(width, height, refresh_mhz);
+            config_head.set_position(monitor.position.x, monitor.position.y);
+            config_head.set_transform(monitor.transform.to_wayland());
+            config_head.set_scale(monitor.scale);
+            Self::set_adaptive_sync(&config_head, monitor.adaptive_sync);
+        }
+
+        Ok(())
+    }
+
+    fn configure_set_scale(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        name: &str,
+        scale: f64,
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        self.find_monitor(name)?;
+
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(WlMonitorManagerError::InvalidScale(scale));
         }
 
         for monitor in self.monitors.values() {
@@ -292,15 +651,9 @@ impl WlMonitorManager {
             }
 
             if !monitor.enabled {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SetScale,
-                    reason: format!(
-                        "Monitor '{}' is disabled, cannot set scale",
-                        name
-                    ),
-                });
-                continue;
+                return Err(WlMonitorManagerError::MonitorDisabled(
+                    name.to_string(),
+                ));
             }
 
             let config_head = config.enable_head(&monitor.head, qh, ());
@@ -310,7 +663,10 @@ impl WlMonitorManager {
             config_head.set_position(monitor.position.x, monitor.position.y);
             config_head.set_transform(monitor.transform.to_wayland());
             config_head.set_scale(scale);
+            Self::set_adaptive_sync(&config_head, monitor.adaptive_sync);
         }
+
+        Ok(())
     }
 
     fn configure_set_transform(
@@ -319,7 +675,9 @@ impl WlMonitorManager {
         name: &str,
         transform: WlTransform,
         qh: &QueueHandle<Self>,
-    ) {
+    ) -> Result<(), WlMonitorManagerError> {
+        self.find_monitor(name)?;
+
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
@@ -327,15 +685,9 @@ impl WlMonitorManager {
             }
 
             if !monitor.enabled {
-                Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SetTransform,
-                    reason: format!(
-                        "Monitor '{}' is disabled, cannot set transform",
-                        name
-                    ),
-                });
-                continue;
+                return Err(WlMonitorManagerError::MonitorDisabled(
+                    name.to_string(),
+                ));
             }
 
             let config_head = config.enable_head(&monitor.head, qh, ());
@@ -345,7 +697,10 @@ impl WlMonitorManager {
             config_head.set_position(monitor.position.x, monitor.position.y);
             config_head.set_transform(transform.to_wayland());
             config_head.set_scale(monitor.scale);
+            Self::set_adaptive_sync(&config_head, monitor.adaptive_sync);
         }
+
+        Ok(())
     }
 
     fn configure_set_position(
@@ -355,7 +710,9 @@ impl WlMonitorManager {
         x: i32,
         y: i32,
         qh: &QueueHandle<Self>,
-    ) {
+    ) -> Result<(), WlMonitorManagerError> {
+        self.find_monitor(name)?;
+
         for monitor in self.monitors.values() {
             if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
@@ -363,28 +720,95 @@ impl WlMonitorManager {
             }
 
             if !monitor.enabled {
+                return Err(WlMonitorManagerError::MonitorDisabled(
+                    name.to_string(),
+                ));
+            }
+
+            let config_head = config.enable_head(&monitor.head, qh, ());
+            if let Some(ref current_mode) = monitor.current_mode {
+                config_head.set_mode(current_mode);
+            }
+            config_head.set_position(x, y);
+            config_head.set_transform(monitor.transform.to_wayland());
+            config_head.set_scale(monitor.scale);
+            Self::set_adaptive_sync(&config_head, monitor.adaptive_sync);
+        }
+
+        Ok(())
+    }
+
+    fn configure_set_adaptive_sync(
+        &self,
+        config: &ZwlrOutputConfigurationV1,
+        name: &str,
+        enabled: bool,
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        self.find_monitor(name)?;
+
+        for monitor in self.monitors.values() {
+            if monitor.name != name {
                 Self::preserve_head(config, monitor, qh);
-                let _ = self.emitter.send(WlMonitorEvent::ActionFailed {
-                    action: ActionKind::SetPosition,
-                    reason: format!(
-                        "Monitor '{}' is disabled, cannot set position",
-                        name
-                    ),
-                });
                 continue;
             }
 
+            if monitor.adaptive_sync == WlAdaptiveSync::Unsupported {
+                return Err(WlMonitorManagerError::AdaptiveSyncUnsupported(
+                    name.to_string(),
+                ));
+            }
+
+            if !monitor.enabled {
+                return Err(WlMonitorManagerError::MonitorDisabled(
+                    name.to_string(),
+                ));
+            }
+
+            let adaptive_sync = if enabled {
+                WlAdaptiveSync::Enabled
+            } else {
+                WlAdaptiveSync::Disabled
+            };
+
             let config_head = config.enable_head(&monitor.head, qh, ());
             if let Some(ref current_mode) = monitor.current_mode {
                 config_head.set_mode(current_mode);
             }
-            config_head.set_position(x, y);
+            config_head.set_position(monitor.position.x, monitor.position.y);
             config_head.set_transform(monitor.transform.to_wayland());
             config_head.set_scale(monitor.scale);
+            Self::set_adaptive_sync(&config_head, adaptive_sync);
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn find_monitor(
+        &self,
+        name: &str,
+    ) -> Result<&WlMonitor, WlMonitorManagerError> {
+        self.monitors
+            .values()
+            .find(|m| m.name == name)
+            .ok_or_else(|| WlMonitorManagerError::UnknownMonitor(name.into()))
+    }
+
+    /// Only sent when both the configuration head and the monitor support
+    /// it, otherwise the compositor would raise a protocol error
+    pub(super) fn set_adaptive_sync(
+        config_head: &ZwlrOutputConfigurationHeadV1,
+        adaptive_sync: WlAdaptiveSync,
+    ) {
+        if config_head.version() < 4 {
+            return;
+        }
+        if let Some(state) = adaptive_sync.to_wayland() {
+            config_head.set_adaptive_sync(state);
         }
     }
 
-    fn preserve_head(
+    pub(super) fn preserve_head(
         config: &ZwlrOutputConfigurationV1,
         monitor: &WlMonitor,
         qh: &QueueHandle<Self>,
@@ -397,6 +821,7 @@ impl WlMonitorManager {
             config_head.set_position(monitor.position.x, monitor.position.y);
             config_head.set_transform(monitor.transform.to_wayland());
             config_head.set_scale(monitor.scale);
+            Self::set_adaptive_sync(&config_head, monitor.adaptive_sync);
         } else {
             config.disable_head(&monitor.head);
         }
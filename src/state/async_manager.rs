@@ -0,0 +1,421 @@
+use std::{
+    collections::HashMap,
+    os::fd::{AsRawFd, RawFd},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures_core::Stream;
+use tokio::{
+    io::{Interest, unix::AsyncFd},
+    sync::{mpsc, oneshot},
+};
+use wayland_client::{Connection, EventQueue};
+
+use super::{
+    ConnectTarget, Emitter, WlActionId, WlMonitorAction, WlMonitorEvent,
+    WlMonitorManager, WlMonitorManagerError, WlMonitorStateHandle,
+    dispatch_error, queue_error, read_events,
+};
+
+type Waiters = Arc<Mutex<HashMap<WlActionId, oneshot::Sender<WlMonitorEvent>>>>;
+
+/// What the handles send to the event loop
+enum Request {
+    Action(WlActionId, WlMonitorAction),
+    ActionTimeout(Duration),
+    MaxRetries(u32),
+}
+
+/// Sends action results to the call awaiting them and everything else to
+/// the event stream
+pub(crate) struct AsyncEmitter {
+    events: mpsc::UnboundedSender<WlMonitorEvent>,
+    waiters: Waiters,
+}
+
+impl AsyncEmitter {
+    pub(crate) fn send(&self, event: WlMonitorEvent) {
+        let id = match &event {
+            WlMonitorEvent::ActionSucceeded { id }
+            | WlMonitorEvent::ActionFailed { id, .. }
+            | WlMonitorEvent::TestResult { id, .. } => Some(*id),
+            _ => None,
+        };
+        let waiter = id.and_then(|id| self.waiters.lock().unwrap().remove(&id));
+        match waiter {
+            Some(waiter) => {
+                let _ = waiter.send(event);
+            }
+            None => {
+                let _ = self.events.send(event);
+            }
+        }
+    }
+}
+
+/// Async front end of [`WlMonitorManager`] for tokio
+///
+/// The event loop runs as a tokio task woken by the Wayland socket, so no
+/// thread or polling interval is needed. Events arrive through the
+/// [`WlMonitorEventStream`] returned with the manager, and each action
+/// resolves to its own result. Clones share the same event loop, which
+/// stops once every clone is dropped or [`Self::shutdown`] is called.
+///
+/// Requires the `async` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// use wlx_monitors::{AsyncWlMonitorManager, WlMonitorAction, WlMonitorEvent};
+///
+/// async fn example() -> Result<(), wlx_monitors::WlMonitorManagerError> {
+///     let (manager, mut events) = AsyncWlMonitorManager::new_connection()?;
+///
+///     if let Some(WlMonitorEvent::InitialState(monitors)) = events.next().await {
+///         println!("{} monitors", monitors.len());
+///     }
+///
+///     manager
+///         .send(WlMonitorAction::SetScale {
+///             name: "DP-1".to_string(),
+///             scale: 1.5,
+///         })
+///         .await
+/// }
+/// ```
+#[derive(Clone)]
+pub struct AsyncWlMonitorManager {
+    requests: mpsc::UnboundedSender<Request>,
+    waiters: Waiters,
+    error: Arc<Mutex<Option<WlMonitorManagerError>>>,
+    state: WlMonitorStateHandle,
+}
+
+impl AsyncWlMonitorManager {
+    /// Connect to `WAYLAND_DISPLAY` and spawn the event loop on the current
+    /// tokio runtime
+    ///
+    /// # Errors
+    ///
+    /// Same as [`WlMonitorManager::new_connection`].
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime.
+    pub fn new_connection()
+    -> Result<(Self, WlMonitorEventStream), WlMonitorManagerError> {
+        let target = ConnectTarget::Env;
+        let conn = target.open()?;
+        Self::start(conn, target)
+    }
+
+    /// Use an existing Wayland connection and spawn the event loop on the
+    /// current tokio runtime
+    ///
+    /// # Errors
+    ///
+    /// Same as [`WlMonitorManager::from_connection`].
+    ///
+    /// # Panics
+    ///
+    /// Panics when called outside of a tokio runtime.
+    pub fn from_connection(
+        conn: Connection,
+    ) -> Result<(Self, WlMonitorEventStream), WlMonitorManagerError> {
+        Self::start(conn, ConnectTarget::Fixed)
+    }
+
+    fn start(
+        conn: Connection,
+        target: ConnectTarget,
+    ) -> Result<(Self, WlMonitorEventStream), WlMonitorManagerError> {
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let waiters = Waiters::default();
+        let emitter = Emitter::Async(AsyncEmitter {
+            events: event_tx,
+            waiters: waiters.clone(),
+        });
+        let (manager, eq) =
+            WlMonitorManager::with_connection(conn, target, emitter, None)?;
+
+        let state = manager.state_handle();
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let error = Arc::default();
+        tokio::spawn(drive(
+            manager,
+            eq,
+            request_rx,
+            waiters.clone(),
+            Arc::clone(&error),
+        ));
+
+        Ok((
+            Self {
+                requests: request_tx,
+                waiters,
+                error,
+                state,
+            },
+            WlMonitorEventStream { rx: event_rx },
+        ))
+    }
+
+    /// Send an action and wait for the compositor to apply it
+    ///
+    /// A [`WlMonitorAction::Test`] resolves to `Ok(())` if the compositor
+    /// would accept it and `ConfigurationFailed` otherwise; use
+    /// [`Self::test`] to get the answer as a `bool`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error `ActionFailed` would carry, or the error that
+    /// stopped the event loop.
+    pub async fn send(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<(), WlMonitorManagerError> {
+        match self.request(action).await? {
+            WlMonitorEvent::ActionFailed { error, .. } => Err(error),
+            WlMonitorEvent::TestResult {
+                succeeded: false, ..
+            } => Err(WlMonitorManagerError::ConfigurationFailed),
+            _ => Ok(()),
+        }
+    }
+
+    /// Ask the compositor whether `action` would succeed without applying it
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the action fails validation or the compositor
+    /// does not answer.
+    pub async fn test(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<bool, WlMonitorManagerError> {
+        match self.request(action.test()).await? {
+            WlMonitorEvent::TestResult { succeeded, .. } => Ok(succeeded),
+            WlMonitorEvent::ActionFailed { error, .. } => Err(error),
+            _ => Ok(true),
+        }
+    }
+
+    /// Stop the output manager and end the event loop, like
+    /// [`WlMonitorAction::Shutdown`]
+    pub async fn shutdown(&self) -> Result<(), WlMonitorManagerError> {
+        self.send(WlMonitorAction::Shutdown).await
+    }
+
+    /// Set how long to wait for the compositor to answer a configuration,
+    /// see [`WlMonitorManager::set_action_timeout`]
+    ///
+    /// Applies to every clone, from the next action on.
+    pub fn set_action_timeout(&self, timeout: Duration) {
+        let _ = self.requests.send(Request::ActionTimeout(timeout));
+    }
+
+    /// Set how many times a cancelled action is retried, see
+    /// [`WlMonitorManager::set_max_retries`]
+    ///
+    /// Applies to every clone.
+    pub fn set_max_retries(&self, max_retries: u32) {
+        let _ = self.requests.send(Request::MaxRetries(max_retries));
+    }
+
+    /// Get a handle to read the current monitors without awaiting events
+    pub fn state_handle(&self) -> WlMonitorStateHandle {
+        self.state.clone()
+    }
+
+    async fn request(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<WlMonitorEvent, WlMonitorManagerError> {
+        let id = WlActionId::next();
+        let (tx, rx) = oneshot::channel();
+        self.waiters.lock().unwrap().insert(id, tx);
+        if self.requests.send(Request::Action(id, action)).is_err() {
+            self.waiters.lock().unwrap().remove(&id);
+            return Err(self.stopped());
+        }
+        rx.await.map_err(|_| self.stopped())
+    }
+
+    /// The error that ended the event loop
+    fn stopped(&self) -> WlMonitorManagerError {
+        self.error.lock().unwrap().clone().unwrap_or_else(|| {
+            WlMonitorManagerError::ConnectionLost(
+                "the event loop has stopped".to_string(),
+            )
+        })
+    }
+}
+
+/// Stream of [`WlMonitorEvent`]s from an [`AsyncWlMonitorManager`]
+///
+/// Results of actions sent through [`AsyncWlMonitorManager`] are returned
+/// by those calls and never appear here. The stream ends when the event
+/// loop stops.
+pub struct WlMonitorEventStream {
+    rx: mpsc::UnboundedReceiver<WlMonitorEvent>,
+}
+
+impl WlMonitorEventStream {
+    /// Wait for the next event, without needing `StreamExt`
+    pub async fn next(&mut self) -> Option<WlMonitorEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for WlMonitorEventStream {
+    type Item = WlMonitorEvent;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+/// The Wayland socket, kept open by the connection handle
+struct Socket(Connection);
+
+impl AsRawFd for Socket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0.backend().poll_fd().as_raw_fd()
+    }
+}
+
+async fn drive(
+    mut manager: WlMonitorManager,
+    mut eq: EventQueue<WlMonitorManager>,
+    mut requests: mpsc::UnboundedReceiver<Request>,
+    waiters: Waiters,
+    error: Arc<Mutex<Option<WlMonitorManagerError>>>,
+) {
+    let result = event_loop(&mut manager, &mut eq, &mut requests).await;
+    let shut_down = result.is_ok();
+    if let Err(e) = result {
+        *error.lock().unwrap() = Some(e);
+    }
+    // Later calls fail to send. Actions already queued fail with
+    // `ConfigurationAborted` after a shutdown, like in `run`, and with the
+    // error that stopped the loop otherwise
+    requests.close();
+    while let Ok(request) = requests.try_recv() {
+        match request {
+            Request::Action(id, action) if shut_down => {
+                manager.abort_action(id, &action);
+            }
+            _ => {}
+        }
+    }
+    // Wake every call still waiting for a result
+    waiters.lock().unwrap().clear();
+}
+
+/// Same steps as [`WlMonitorManager::run`], waiting on the runtime instead
+/// of `poll`
+async fn event_loop(
+    manager: &mut WlMonitorManager,
+    eq: &mut EventQueue<WlMonitorManager>,
+    requests: &mut mpsc::UnboundedReceiver<Request>,
+) -> Result<(), WlMonitorManagerError> {
+    let qh = eq.handle();
+    let socket = Socket(manager.conn.clone());
+    // SAFETY: `Socket` owns a handle to the connection, which keeps its
+    // socket open for as long as the `AsyncFd` exists, and always returns
+    // the same file descriptor
+    let fd =
+        unsafe { AsyncFd::register_with_interest(socket, Interest::READABLE) }
+            .map_err(|e| {
+                WlMonitorManagerError::ConnectionError(e.to_string())
+            })?;
+
+    loop {
+        eq.flush().map_err(queue_error)?;
+
+        let mut received = None;
+        if let Some(guard) = eq.prepare_read() {
+            let deadline = manager.next_deadline();
+            tokio::select! {
+                ready = fd.readable() => {
+                    let mut ready = ready.map_err(|e| {
+                        WlMonitorManagerError::ConnectionLost(e.to_string())
+                    })?;
+                    if !read_events(guard)? {
+                        ready.clear_ready();
+                    }
+                }
+                request = requests.recv() => {
+                    drop(guard);
+                    match request {
+                        Some(request) => received = Some(request),
+                        // Every handle was dropped
+                        None => return Ok(()),
+                    }
+                }
+                _ = tokio::time::sleep_until(
+                    deadline.unwrap_or_else(Instant::now).into()
+                ), if deadline.is_some() => {}
+            }
+        }
+
+        eq.dispatch_pending(manager).map_err(dispatch_error)?;
+        manager.expire_pending();
+        manager.retry_cancelled(&qh);
+
+        let queued = std::iter::from_fn(|| requests.try_recv().ok());
+        for request in received.into_iter().chain(queued) {
+            match request {
+                Request::Action(id, WlMonitorAction::Shutdown) => {
+                    return shutdown(manager, eq, &fd, id).await;
+                }
+                Request::Action(id, action) => {
+                    manager.handle_action(id, action, &qh);
+                }
+                Request::ActionTimeout(timeout) => {
+                    manager.set_action_timeout(timeout);
+                }
+                Request::MaxRetries(max_retries) => {
+                    manager.set_max_retries(max_retries);
+                }
+            }
+        }
+    }
+}
+
+/// Same as [`WlMonitorManager::shutdown`], waiting for the compositor's
+/// `finished` event on the runtime instead of blocking it
+async fn shutdown(
+    manager: &mut WlMonitorManager,
+    eq: &mut EventQueue<WlMonitorManager>,
+    fd: &AsyncFd<Socket>,
+    id: WlActionId,
+) -> Result<(), WlMonitorManagerError> {
+    let deadline = manager.begin_shutdown();
+    while !manager.finished {
+        eq.flush().map_err(queue_error)?;
+        if let Some(guard) = eq.prepare_read() {
+            tokio::select! {
+                ready = fd.readable() => {
+                    let mut ready = ready.map_err(|e| {
+                        WlMonitorManagerError::ConnectionLost(e.to_string())
+                    })?;
+                    if !read_events(guard)? {
+                        ready.clear_ready();
+                    }
+                }
+                // The compositor did not answer; release everything anyway
+                _ = tokio::time::sleep_until(deadline.into()) => break,
+            }
+        }
+        eq.dispatch_pending(manager).map_err(dispatch_error)?;
+    }
+    manager.finish_shutdown(id);
+    eq.flush().map_err(queue_error)
+}
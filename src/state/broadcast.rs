@@ -0,0 +1,116 @@
+use std::{
+    os::fd::{AsFd, OwnedFd},
+    sync::{
+        Arc, Mutex, Weak,
+        mpsc::{Receiver, sync_channel},
+    },
+};
+
+use super::{
+    WlEventFilter, WlMonitorEvent, WlMonitorManager, filter::Subscriber,
+};
+
+/// Hands out event receivers for a running [`WlMonitorManager`]
+///
+/// Obtained from [`WlMonitorManager::event_subscriber`] before calling
+/// `run`; clones can then be passed to any thread, and every call to
+/// [`Self::subscribe`] returns a new receiver. Each receiver first gets an
+/// [`WlMonitorEvent::InitialState`] with the monitors as of the last
+/// complete update from the compositor, followed by the same events as
+/// every other subscriber, so nothing is missed or reported twice.
+///
+/// # Example
+///
+/// ```no_run
+/// use wlx_monitors::{WlMonitorEvent, WlMonitorManager, action_channel};
+/// use std::sync::mpsc::sync_channel;
+///
+/// let (tx, rx) = sync_channel(10);
+/// let (action_tx, action_rx) = action_channel(10);
+/// let (manager, event_queue) = WlMonitorManager::new_connection(tx, action_rx).unwrap();
+///
+/// let subscriber = manager.event_subscriber();
+/// std::thread::spawn(move || manager.run(event_queue));
+///
+/// // Later, from another component
+/// let events = subscriber.subscribe(10);
+/// if let Ok(WlMonitorEvent::InitialState(monitors)) = events.recv() {
+///     println!("{} monitors", monitors.len());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct WlEventSubscriber {
+    pending: Weak<Mutex<Vec<Subscriber>>>,
+    wake: Option<Arc<OwnedFd>>,
+}
+
+impl WlEventSubscriber {
+    /// Receive every event through a new channel holding up to `bound`
+    /// events
+    ///
+    /// The receiver disconnects once the manager stops, right away if it
+    /// already has.
+    pub fn subscribe(&self, bound: usize) -> Receiver<WlMonitorEvent> {
+        self.subscribe_filtered(bound, WlEventFilter::new())
+    }
+
+    /// Receive the events selected by `filter` through a new channel
+    /// holding up to `bound` events
+    ///
+    /// The initial state only lists the monitors the filter selects, and is
+    /// left out if the filter does not include
+    /// [`WlEventKinds::INITIAL_STATE`](super::WlEventKinds::INITIAL_STATE).
+    pub fn subscribe_filtered(
+        &self,
+        bound: usize,
+        filter: WlEventFilter,
+    ) -> Receiver<WlMonitorEvent> {
+        let (tx, rx) = sync_channel(bound);
+        // Dropping `tx` disconnects the receiver if the manager is gone
+        if let Some(pending) = self.pending.upgrade() {
+            pending.lock().unwrap().push(Subscriber::new(tx, filter));
+            if let Some(wake) = &self.wake {
+                let _ = rustix::io::write(wake.as_fd(), &1u64.to_ne_bytes());
+            }
+        }
+        rx
+    }
+}
+
+impl WlMonitorManager {
+    /// Get a handle that adds event subscribers while `run` is executing
+    ///
+    /// Like [`Self::subscribe`], this only applies to managers sending
+    /// their events over channels. With a plain `Receiver` as controller,
+    /// new subscribers are picked up within 50 ms instead of right away.
+    pub fn event_subscriber(&self) -> WlEventSubscriber {
+        WlEventSubscriber {
+            pending: Arc::downgrade(&self.new_subscribers),
+            wake: self.controller.as_ref().and_then(|c| c.waker()),
+        }
+    }
+
+    /// Register the subscribers added through [`WlEventSubscriber`], telling
+    /// each the state published at the last `done` event first
+    ///
+    /// While an update is in progress, they wait for its `done` event: the
+    /// published state may still list a monitor whose `Removed` event the
+    /// other subscribers already received.
+    pub(super) fn add_subscribers(&mut self) {
+        if !self.initialized || self.updating {
+            return;
+        }
+        let subscribers =
+            std::mem::take(&mut *self.new_subscribers.lock().unwrap());
+        if subscribers.is_empty() {
+            return;
+        }
+        let monitors = self.state.monitors();
+        for mut subscriber in subscribers {
+            let initial = WlMonitorEvent::InitialState(monitors.clone());
+            if subscriber.send(&initial) {
+                self.emitter.subscribe(subscriber);
+            }
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    cell::RefCell,
+    os::fd::OwnedFd,
+    sync::mpsc::SendError,
+    time::{Duration, Instant},
+};
+
+use calloop::{
+    EventSource, Interest, Mode, Poll, PostAction, Readiness, Token,
+    TokenFactory,
+    channel::{self, Channel},
+    generic::Generic,
+};
+use rustix::time::{
+    Itimerspec, TimerfdClockId, TimerfdFlags, TimerfdTimerFlags,
+    timerfd_create, timerfd_settime,
+};
+use wayland_client::{Connection, EventQueue};
+
+use super::{
+    ConnectTarget, Emitter, WlActionId, WlMonitorAction, WlMonitorEvent,
+    WlMonitorManager, WlMonitorManagerError, WlMonitorStateHandle,
+    dispatch_error, queue_error, read_events, timespec,
+};
+
+/// [`WlMonitorManager`] as a calloop [`EventSource`]
+///
+/// Insert it into an existing calloop event loop instead of running
+/// [`WlMonitorManager::run`] on its own thread. Every [`WlMonitorEvent`] is
+/// passed to the callback, and actions are sent through the
+/// [`WlEventSourceSender`] returned with the source, which returns the
+/// [`WlActionId`] their results will carry. The source removes itself after
+/// a [`WlMonitorAction::Shutdown`], once the compositor acknowledged it or
+/// the action timeout passed, without blocking the loop in between.
+///
+/// Requires the `calloop` feature.
+///
+/// # Example
+///
+/// ```no_run
+/// use calloop::EventLoop;
+/// use wlx_monitors::{WlMonitorEvent, WlMonitorEventSource};
+///
+/// let mut event_loop: EventLoop<()> = EventLoop::try_new().unwrap();
+/// let (source, actions) = WlMonitorEventSource::new_connection().unwrap();
+///
+/// event_loop
+///     .handle()
+///     .insert_source(source, |event, _, _| {
+///         if let WlMonitorEvent::Changed { monitor, .. } = event {
+///             println!("{} changed", monitor.name);
+///         }
+///     })
+///     .unwrap();
+/// ```
+pub struct WlMonitorEventSource {
+    manager: WlMonitorManager,
+    eq: EventQueue<WlMonitorManager>,
+    socket: Generic<Connection>,
+    actions: Channel<(WlActionId, WlMonitorAction)>,
+    timer: Generic<OwnedFd>,
+    /// The `Shutdown` action being carried out and when to stop waiting
+    /// for the compositor
+    shutdown: Option<(WlActionId, Instant)>,
+}
+
+impl WlMonitorEventSource {
+    /// Connect to `WAYLAND_DISPLAY`
+    ///
+    /// # Errors
+    ///
+    /// Same as [`WlMonitorManager::new_connection`].
+    pub fn new_connection()
+    -> Result<(Self, WlEventSourceSender), WlMonitorManagerError> {
+        let target = ConnectTarget::Env;
+        let conn = target.open()?;
+        Self::start(conn, target)
+    }
+
+    /// Use an existing Wayland connection
+    ///
+    /// # Errors
+    ///
+    /// Same as [`WlMonitorManager::from_connection`].
+    pub fn from_connection(
+        conn: Connection,
+    ) -> Result<(Self, WlEventSourceSender), WlMonitorManagerError> {
+        Self::start(conn, ConnectTarget::Fixed)
+    }
+
+    fn start(
+        conn: Connection,
+        target: ConnectTarget,
+    ) -> Result<(Self, WlEventSourceSender), WlMonitorManagerError> {
+        let socket = Generic::new(conn.clone(), Interest::READ, Mode::Level);
+        let (manager, eq) = WlMonitorManager::with_connection(
+            conn,
+            target,
+            Emitter::Queue(RefCell::default()),
+            None,
+        )?;
+        let timer = timerfd_create(
+            TimerfdClockId::Monotonic,
+            TimerfdFlags::CLOEXEC | TimerfdFlags::NONBLOCK,
+        )
+        .map_err(|e| WlMonitorManagerError::ConnectionError(e.to_string()))?;
+
+        let (tx, actions) = channel::channel();
+        let source = Self {
+            manager,
+            eq,
+            socket,
+            actions,
+            timer: Generic::new(timer, Interest::READ, Mode::Level),
+            shutdown: None,
+        };
+        // The initial state may already be queued from the registry
+        // roundtrip, with nothing left to read on the socket
+        source.arm_timer()?;
+
+        Ok((source, WlEventSourceSender { tx }))
+    }
+
+    /// Set how long to wait for the compositor to answer a configuration,
+    /// see [`WlMonitorManager::set_action_timeout`]
+    pub fn set_action_timeout(&mut self, timeout: Duration) {
+        self.manager.set_action_timeout(timeout);
+    }
+
+    /// Set how many times a cancelled action is retried, see
+    /// [`WlMonitorManager::set_max_retries`]
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.manager.set_max_retries(max_retries);
+    }
+
+    /// Get a handle to read the current monitors outside of the callback
+    pub fn state_handle(&self) -> WlMonitorStateHandle {
+        self.manager.state_handle()
+    }
+
+    /// Wake the loop when the next configuration expires or a shutdown
+    /// stops waiting, or right away if events are waiting for the callback
+    fn arm_timer(&self) -> Result<(), WlMonitorManagerError> {
+        let queued = match &self.manager.emitter {
+            Emitter::Queue(queue) => !queue.borrow().is_empty(),
+            _ => false,
+        };
+        let now = Instant::now();
+        let deadline = if queued {
+            Some(now)
+        } else {
+            let shutdown = self.shutdown.map(|(_, deadline)| deadline);
+            self.manager
+                .next_deadline()
+                .into_iter()
+                .chain(shutdown)
+                .min()
+        };
+        let it_value = match deadline {
+            // A zero value would disarm the timer
+            Some(deadline) => {
+                let timeout = deadline
+                    .saturating_duration_since(now)
+                    .max(Duration::from_nanos(1));
+                timespec(timeout)
+            }
+            None => timespec(Duration::ZERO),
+        };
+        timerfd_settime(
+            self.timer.get_ref(),
+            TimerfdTimerFlags::empty(),
+            &Itimerspec {
+                it_interval: timespec(Duration::ZERO),
+                it_value,
+            },
+        )
+        .map(|_| ())
+        .map_err(|e| WlMonitorManagerError::EventQueueError(e.to_string()))
+    }
+
+    /// Same steps as one iteration of [`WlMonitorManager::run`]; returns
+    /// `true` once the manager was shut down
+    ///
+    /// A `Shutdown` only asks the compositor to stop: later iterations wait
+    /// for its `finished` event, bounded by the timer, and the actions
+    /// arriving meanwhile are aborted.
+    fn process(
+        &mut self,
+        received: Vec<(WlActionId, WlMonitorAction)>,
+    ) -> Result<bool, WlMonitorManagerError> {
+        if let Some(guard) = self.eq.prepare_read() {
+            read_events(guard)?;
+        }
+
+        let qh = self.eq.handle();
+        self.eq
+            .dispatch_pending(&mut self.manager)
+            .map_err(dispatch_error)?;
+        self.manager.expire_pending();
+        self.manager.retry_cancelled(&qh);
+
+        for (id, action) in received {
+            if self.shutdown.is_some() {
+                self.manager.abort_action(id, &action);
+            } else if let WlMonitorAction::Shutdown = action {
+                self.shutdown = Some((id, self.manager.begin_shutdown()));
+            } else {
+                self.manager.handle_action(id, action, &qh);
+            }
+        }
+        self.eq.flush().map_err(queue_error)?;
+
+        let Some((id, deadline)) = self.shutdown else {
+            return Ok(false);
+        };
+        // Without an answer in time, release everything anyway
+        if !self.manager.finished && Instant::now() < deadline {
+            return Ok(false);
+        }
+        self.manager.finish_shutdown(id);
+        self.eq.flush().map_err(queue_error)?;
+        Ok(true)
+    }
+}
+
+/// Sends actions to a [`WlMonitorEventSource`]
+///
+/// Sending wakes the calloop event loop and never blocks.
+#[derive(Clone)]
+pub struct WlEventSourceSender {
+    tx: channel::Sender<(WlActionId, WlMonitorAction)>,
+}
+
+impl WlEventSourceSender {
+    /// Send an action
+    ///
+    /// Returns the generated ID that the resulting events will carry.
+    pub fn send(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<WlActionId, SendError<WlMonitorAction>> {
+        let id = WlActionId::next();
+        self.send_with_id(id, action)?;
+        Ok(id)
+    }
+
+    /// Send an action with a caller-supplied ID
+    pub fn send_with_id(
+        &self,
+        id: WlActionId,
+        action: WlMonitorAction,
+    ) -> Result<(), SendError<WlMonitorAction>> {
+        self.tx
+            .send((id, action))
+            .map_err(|SendError((_, action))| SendError(action))
+    }
+}
+
+impl EventSource for WlMonitorEventSource {
+    type Event = WlMonitorEvent;
+    type Metadata = ();
+    type Ret = ();
+    type Error = WlMonitorManagerError;
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> Result<PostAction, Self::Error>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let mut received = Vec::new();
+        self.actions
+            .process_events(readiness, token, |event, _| {
+                if let channel::Event::Msg(action) = event {
+                    received.push(action);
+                }
+            })
+            .map_err(|e| {
+                WlMonitorManagerError::EventQueueError(e.to_string())
+            })?;
+        // Only clear the timer; the socket is read below regardless of
+        // which file descriptor woke the loop
+        let mut buf = [0u8; 8];
+        let _ = rustix::io::read(self.timer.get_ref(), &mut buf);
+
+        let stopped = self.process(received)?;
+
+        let events = match &self.manager.emitter {
+            Emitter::Queue(queue) => queue.take(),
+            _ => Vec::new(),
+        };
+        for event in events {
+            callback(event, &mut ());
+        }
+
+        if stopped {
+            return Ok(PostAction::Remove);
+        }
+        self.arm_timer()?;
+        Ok(PostAction::Continue)
+    }
+
+    fn register(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.socket.register(poll, token_factory)?;
+        self.actions.register(poll, token_factory)?;
+        self.timer.register(poll, token_factory)
+    }
+
+    fn reregister(
+        &mut self,
+        poll: &mut Poll,
+        token_factory: &mut TokenFactory,
+    ) -> calloop::Result<()> {
+        self.socket.reregister(poll, token_factory)?;
+        self.actions.reregister(poll, token_factory)?;
+        self.timer.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+        self.socket.unregister(poll)?;
+        self.actions.unregister(poll)?;
+        self.timer.unregister(poll)
+    }
+}
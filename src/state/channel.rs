@@ -0,0 +1,172 @@
+use std::{
+    os::fd::{AsFd, BorrowedFd, OwnedFd},
+    sync::{
+        Arc,
+        mpsc::{
+            Receiver, SendError, SyncSender, TryRecvError, TrySendError,
+            sync_channel,
+        },
+    },
+};
+
+use rustix::event::{EventfdFlags, eventfd};
+
+use super::{WlActionId, WlMonitorAction};
+
+/// Create a bounded action channel that wakes the event loop as soon as an
+/// action is sent
+///
+/// Pass the receiver to [`WlMonitorManager::new_connection`] instead of a
+/// plain [`Receiver`] so actions are handled immediately rather than on the
+/// next polling interval.
+///
+/// [`WlMonitorManager::new_connection`]: super::WlMonitorManager::new_connection
+///
+/// # Example
+///
+/// ```no_run
+/// use wlx_monitors::{WlMonitorManager, action_channel};
+/// use std::sync::mpsc::sync_channel;
+///
+/// let (tx, rx) = sync_channel(10);
+/// let (action_tx, action_rx) = action_channel(10);
+///
+/// let (manager, event_queue) = WlMonitorManager::new_connection(tx, action_rx).unwrap();
+/// ```
+pub fn action_channel(bound: usize) -> (WlActionSender, WlActionReceiver) {
+    let (tx, rx) = sync_channel(bound);
+    let rx = ActionRx::Tagged(rx);
+    // Without an eventfd the event loop falls back to polling the channel
+    let wake = eventfd(0, EventfdFlags::CLOEXEC | EventfdFlags::NONBLOCK)
+        .ok()
+        .map(Arc::new);
+    (
+        WlActionSender {
+            tx,
+            wake: wake.clone(),
+        },
+        WlActionReceiver { rx, wake },
+    )
+}
+
+/// Sending half of an [`action_channel`]
+#[derive(Clone)]
+pub struct WlActionSender {
+    tx: SyncSender<(WlActionId, WlMonitorAction)>,
+    wake: Option<Arc<OwnedFd>>,
+}
+
+impl WlActionSender {
+    /// Send an action, blocking while the channel is full
+    ///
+    /// Returns the generated ID that the resulting events will carry.
+    pub fn send(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<WlActionId, SendError<WlMonitorAction>> {
+        let id = WlActionId::next();
+        self.send_with_id(id, action)?;
+        Ok(id)
+    }
+
+    /// Send an action with a caller-supplied ID, blocking while the channel
+    /// is full
+    pub fn send_with_id(
+        &self,
+        id: WlActionId,
+        action: WlMonitorAction,
+    ) -> Result<(), SendError<WlMonitorAction>> {
+        self.tx
+            .send((id, action))
+            .map_err(|SendError((_, action))| SendError(action))?;
+        self.wake();
+        Ok(())
+    }
+
+    /// Send an action without blocking
+    ///
+    /// Returns the generated ID that the resulting events will carry.
+    pub fn try_send(
+        &self,
+        action: WlMonitorAction,
+    ) -> Result<WlActionId, TrySendError<WlMonitorAction>> {
+        let id = WlActionId::next();
+        self.try_send_with_id(id, action)?;
+        Ok(id)
+    }
+
+    /// Send an action with a caller-supplied ID without blocking
+    pub fn try_send_with_id(
+        &self,
+        id: WlActionId,
+        action: WlMonitorAction,
+    ) -> Result<(), TrySendError<WlMonitorAction>> {
+        self.tx.try_send((id, action)).map_err(|e| match e {
+            TrySendError::Full((_, action)) => TrySendError::Full(action),
+            TrySendError::Disconnected((_, action)) => {
+                TrySendError::Disconnected(action)
+            }
+        })?;
+        self.wake();
+        Ok(())
+    }
+
+    fn wake(&self) {
+        if let Some(wake) = &self.wake {
+            let _ = rustix::io::write(wake.as_fd(), &1u64.to_ne_bytes());
+        }
+    }
+}
+
+/// Receiving half of an [`action_channel`]
+///
+/// A plain [`Receiver`] converts into a `WlActionReceiver` that is polled
+/// periodically instead of waking the event loop. Actions received through
+/// it get a generated [`WlActionId`].
+pub struct WlActionReceiver {
+    rx: ActionRx,
+    wake: Option<Arc<OwnedFd>>,
+}
+
+enum ActionRx {
+    Plain(Receiver<WlMonitorAction>),
+    Tagged(Receiver<(WlActionId, WlMonitorAction)>),
+}
+
+impl WlActionReceiver {
+    pub(crate) fn wake_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.wake.as_ref().map(|wake| wake.as_fd())
+    }
+
+    /// The eventfd that wakes the event loop, to share with other handles
+    pub(crate) fn waker(&self) -> Option<Arc<OwnedFd>> {
+        self.wake.clone()
+    }
+
+    /// Reset the wake-up counter; must happen before draining the channel
+    /// so that actions sent while draining wake the loop again
+    pub(crate) fn clear_wake(&self) {
+        if let Some(wake) = &self.wake {
+            let mut buf = [0u8; 8];
+            let _ = rustix::io::read(wake.as_fd(), &mut buf);
+        }
+    }
+
+    pub(crate) fn try_recv(
+        &self,
+    ) -> Result<(WlActionId, WlMonitorAction), TryRecvError> {
+        match &self.rx {
+            ActionRx::Plain(rx) => Ok((WlActionId::next(), rx.try_recv()?)),
+            ActionRx::Tagged(rx) => rx.try_recv(),
+        }
+    }
+}
+
+impl From<Receiver<WlMonitorAction>> for WlActionReceiver {
+    fn from(rx: Receiver<WlMonitorAction>) -> Self {
+        Self {
+            rx: ActionRx::Plain(rx),
+            wake: None,
+        }
+    }
+}
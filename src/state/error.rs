@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use crate::wl_monitor::WlRefreshRate;
+
+use super::ActionKind;
+
+/// Errors that can occur when using the monitor manager
+///
+/// Returned by [`WlMonitorManager`](super::WlMonitorManager) itself and
+/// carried by [`WlMonitorEvent::ActionFailed`](super::WlMonitorEvent).
+#[derive(Debug, Clone, PartialEq)]
+pub enum WlMonitorManagerError {
+    /// Failed to establish Wayland connection
+    ConnectionError(String),
+    /// Error in the Wayland event queue
+    EventQueueError(String),
+    /// The connection to the compositor was closed
+    ConnectionLost(String),
+    /// The compositor does not provide a required protocol global
+    ProtocolUnsupported(&'static str),
+    /// The compositor has not sent the monitor state yet
+    NotReady,
+    /// The compositor rejected the configuration
+    ConfigurationFailed,
+    /// The configuration was cancelled because the monitor state changed
+    /// before it was applied
+    ConfigurationCancelled,
+    /// The compositor did not answer the configuration in time
+    ConfigurationTimedOut(Duration),
+    /// The manager shut down before the compositor answered
+    ConfigurationAborted,
+    /// The compositor rejected a custom mode
+    CustomModeRejected {
+        name: String,
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
+    },
+    /// No monitor with this name is connected
+    UnknownMonitor(String),
+    /// The monitor appears more than once in a transaction
+    DuplicateMonitor(String),
+    /// The monitor has no mode matching the request
+    NoMatchingMode {
+        name: String,
+        width: i32,
+        height: i32,
+        refresh_rate: WlRefreshRate,
+    },
+    /// The monitor has no mode it could be enabled with
+    NoValidMode(String),
+    /// The action requires the monitor to be enabled
+    MonitorDisabled(String),
+    /// The scale factor is not finite or not > 0
+    InvalidScale(f64),
+    /// The custom mode has a non-positive size or a negative refresh rate
+    InvalidCustomMode {
+        width: i32,
+        height: i32,
+        refresh_mhz: i32,
+    },
+    /// The compositor cannot control adaptive sync for this monitor
+    AdaptiveSyncUnsupported(String),
+    /// The action cannot be sent as a test
+    NotTestable(ActionKind),
+}
+
+impl std::fmt::Display for WlMonitorManagerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ConnectionError(e) => {
+                write!(f, "failed to connect to Wayland: {}", e)
+            }
+            Self::EventQueueError(e) => write!(f, "Wayland event queue: {}", e),
+            Self::ConnectionLost(e) => {
+                write!(f, "lost the connection to the compositor: {}", e)
+            }
+            Self::ProtocolUnsupported(interface) => {
+                write!(f, "compositor does not support {}", interface)
+            }
+            Self::NotReady => {
+                write!(f, "compositor has not sent the monitor state yet")
+            }
+            Self::ConfigurationFailed => {
+                write!(f, "compositor rejected the configuration")
+            }
+            Self::ConfigurationCancelled => {
+                write!(f, "configuration cancelled (serial outdated)")
+            }
+            Self::ConfigurationTimedOut(timeout) => {
+                write!(f, "compositor did not answer within {:?}", timeout)
+            }
+            Self::ConfigurationAborted => {
+                write!(f, "manager shut down before the compositor answered")
+            }
+            Self::CustomModeRejected {
+                name,
+                width,
+                height,
+                refresh_mhz,
+            } => write!(
+                f,
+                "compositor rejected custom mode {}x{}@{}mHz for monitor '{}'",
+                width, height, refresh_mhz, name
+            ),
+            Self::UnknownMonitor(name) => {
+                write!(f, "unknown monitor '{}'", name)
+            }
+            Self::DuplicateMonitor(name) => write!(
+                f,
+                "monitor '{}' appears more than once in the transaction",
+                name
+            ),
+            Self::NoMatchingMode {
+                name,
+                width,
+                height,
+                refresh_rate,
+            } => write!(
+                f,
+                "no matching mode {}x{}@{} for monitor '{}'",
+                width, height, refresh_rate, name
+            ),
+            Self::NoValidMode(name) => {
+                write!(f, "no valid mode available for monitor '{}'", name)
+            }
+            Self::MonitorDisabled(name) => {
+                write!(f, "monitor '{}' is disabled", name)
+            }
+            Self::InvalidScale(scale) => write!(
+                f,
+                "invalid scale value '{}': must be finite and > 0",
+                scale
+            ),
+            Self::InvalidCustomMode {
+                width,
+                height,
+                refresh_mhz,
+            } => write!(
+                f,
+                "invalid custom mode {}x{}@{}mHz: size must be > 0 and refresh >= 0",
+                width, height, refresh_mhz
+            ),
+            Self::AdaptiveSyncUnsupported(name) => write!(
+                f,
+                "compositor does not support adaptive sync for monitor '{}'",
+                name
+            ),
+            Self::NotTestable(kind) => {
+                write!(f, "{:?} actions cannot be tested", kind)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WlMonitorManagerError {}
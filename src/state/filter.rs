@@ -0,0 +1,309 @@
+use std::{collections::HashSet, sync::mpsc::SyncSender};
+
+use wayland_client::backend::ObjectId;
+
+use crate::wl_monitor::{WlMonitor, WlMonitorChanges};
+
+use super::{WlMonitorEvent, WlMonitorManager};
+
+bitflags::bitflags! {
+    /// Kinds of [`WlMonitorEvent`] a [`WlEventFilter`] lets through
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct WlEventKinds: u32 {
+        /// `InitialState`
+        const INITIAL_STATE = 1 << 0;
+        /// `Changed`
+        const CHANGED = 1 << 1;
+        /// `Added`
+        const ADDED = 1 << 2;
+        /// `Removed`
+        const REMOVED = 1 << 3;
+        /// `ActionSucceeded`, `ActionFailed` and `TestResult`
+        const ACTION_RESULT = 1 << 4;
+        /// `OutputManagerRemoved`, `Disconnected` and `Reconnected`
+        const CONNECTION = 1 << 5;
+        /// `Added` and `Removed`
+        const HOTPLUG = Self::ADDED.bits() | Self::REMOVED.bits();
+    }
+}
+
+impl WlMonitorEvent {
+    /// The kind of this event, as used by [`WlEventFilter::kinds`]
+    pub fn kind(&self) -> WlEventKinds {
+        match self {
+            Self::InitialState(_) => WlEventKinds::INITIAL_STATE,
+            Self::Changed { .. } => WlEventKinds::CHANGED,
+            Self::Added(_) => WlEventKinds::ADDED,
+            Self::Removed { .. } => WlEventKinds::REMOVED,
+            Self::ActionSucceeded { .. }
+            | Self::ActionFailed { .. }
+            | Self::TestResult { .. } => WlEventKinds::ACTION_RESULT,
+            Self::OutputManagerRemoved
+            | Self::Disconnected
+            | Self::Reconnected => WlEventKinds::CONNECTION,
+        }
+    }
+}
+
+/// A monitor selected by a [`WlEventFilter`]
+#[derive(Debug, Clone, PartialEq)]
+enum MonitorMatch {
+    Name(String),
+    Identity {
+        make: String,
+        model: String,
+        serial_number: String,
+    },
+}
+
+impl MonitorMatch {
+    fn matches(&self, monitor: &WlMonitor) -> bool {
+        match self {
+            Self::Name(name) => monitor.name == *name,
+            Self::Identity {
+                make,
+                model,
+                serial_number,
+            } => {
+                monitor.make == *make
+                    && monitor.model == *model
+                    && monitor.serial_number == *serial_number
+            }
+        }
+    }
+}
+
+/// Selects which [`WlMonitorEvent`]s a subscriber receives
+///
+/// The default filter lets every event through; each builder method
+/// narrows it down. Monitor criteria apply to `InitialState` (which only
+/// lists the matching monitors), `Changed`, `Added` and `Removed`, and
+/// changed-property criteria apply to `Changed` only.
+///
+/// # Example
+///
+/// ```
+/// use wlx_monitors::{WlEventFilter, WlEventKinds, WlMonitorChanges};
+///
+/// // Only hotplug events
+/// let tray = WlEventFilter::new().kinds(WlEventKinds::HOTPLUG);
+///
+/// // Only scale changes of DP-1
+/// let scale_widget = WlEventFilter::new()
+///     .kinds(WlEventKinds::INITIAL_STATE | WlEventKinds::CHANGED)
+///     .monitor("DP-1")
+///     .changes(WlMonitorChanges::SCALE);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct WlEventFilter {
+    kinds: WlEventKinds,
+    monitors: Vec<MonitorMatch>,
+    changes: WlMonitorChanges,
+}
+
+impl Default for WlEventFilter {
+    fn default() -> Self {
+        Self {
+            kinds: WlEventKinds::all(),
+            monitors: Vec::new(),
+            changes: WlMonitorChanges::all(),
+        }
+    }
+}
+
+impl WlEventFilter {
+    /// A filter that lets every event through
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only pass events of these kinds
+    pub fn kinds(mut self, kinds: WlEventKinds) -> Self {
+        self.kinds = kinds;
+        self
+    }
+
+    /// Only pass monitor events for the monitor with this connector name
+    /// (e.g. `DP-1`); can be combined with further monitors
+    pub fn monitor(mut self, name: impl Into<String>) -> Self {
+        self.monitors.push(MonitorMatch::Name(name.into()));
+        self
+    }
+
+    /// Only pass monitor events for the display with this make, model and
+    /// serial number, whichever connector it is plugged into; can be
+    /// combined with further monitors
+    pub fn identity(
+        mut self,
+        make: impl Into<String>,
+        model: impl Into<String>,
+        serial_number: impl Into<String>,
+    ) -> Self {
+        self.monitors.push(MonitorMatch::Identity {
+            make: make.into(),
+            model: model.into(),
+            serial_number: serial_number.into(),
+        });
+        self
+    }
+
+    /// Only pass `Changed` events where at least one of these properties
+    /// changed
+    pub fn changes(mut self, changes: WlMonitorChanges) -> Self {
+        self.changes = changes;
+        self
+    }
+
+    /// Whether `monitor` is selected by the monitor criteria
+    pub fn matches_monitor(&self, monitor: &WlMonitor) -> bool {
+        self.monitors.is_empty()
+            || self.monitors.iter().any(|m| m.matches(monitor))
+    }
+}
+
+/// An event channel registered on the manager, with the filter deciding
+/// what is sent over it
+pub(crate) struct Subscriber {
+    tx: SyncSender<WlMonitorEvent>,
+    filter: WlEventFilter,
+    /// Heads this subscriber was told about, so `Removed` (which only
+    /// carries the name) can be matched by identity too
+    heads: HashSet<ObjectId>,
+}
+
+impl Subscriber {
+    pub(super) fn new(
+        tx: SyncSender<WlMonitorEvent>,
+        filter: WlEventFilter,
+    ) -> Self {
+        Self {
+            tx,
+            filter,
+            heads: HashSet::new(),
+        }
+    }
+
+    /// Send `event` if the filter selects it; returns `false` once the
+    /// receiving side is gone
+    pub(crate) fn send(&mut self, event: &WlMonitorEvent) -> bool {
+        match self.select(event) {
+            Some(event) => self.tx.send(event).is_ok(),
+            None => true,
+        }
+    }
+
+    /// The part of `event` this subscriber receives, if any
+    fn select(&mut self, event: &WlMonitorEvent) -> Option<WlMonitorEvent> {
+        let filter = &self.filter;
+        if !filter.kinds.contains(event.kind()) {
+            // Still follow the monitors for a later `Removed`
+            self.track(event);
+            return None;
+        }
+        let event = match event {
+            WlMonitorEvent::InitialState(monitors) => {
+                WlMonitorEvent::InitialState(
+                    monitors
+                        .iter()
+                        .filter(|m| filter.matches_monitor(m))
+                        .cloned()
+                        .collect(),
+                )
+            }
+            WlMonitorEvent::Changed {
+                monitor,
+                changes,
+                previous,
+                ..
+            } => {
+                if !changes.intersects(filter.changes)
+                    || !(filter.matches_monitor(monitor)
+                        || filter.matches_monitor(previous))
+                {
+                    self.track(event);
+                    return None;
+                }
+                event.clone()
+            }
+            WlMonitorEvent::Added(monitor) => {
+                if !filter.matches_monitor(monitor) {
+                    return None;
+                }
+                event.clone()
+            }
+            WlMonitorEvent::Removed { id, .. } => {
+                if !self.heads.contains(id) {
+                    return None;
+                }
+                event.clone()
+            }
+            _ => event.clone(),
+        };
+        self.track(&event);
+        Some(event)
+    }
+
+    /// Remember which heads match the filter
+    fn track(&mut self, event: &WlMonitorEvent) {
+        match event {
+            WlMonitorEvent::InitialState(monitors) => {
+                self.heads = monitors
+                    .iter()
+                    .filter(|m| self.filter.matches_monitor(m))
+                    .map(|m| m.head_id.clone())
+                    .collect();
+            }
+            WlMonitorEvent::Changed { monitor, .. }
+            | WlMonitorEvent::Added(monitor) => {
+                if self.filter.matches_monitor(monitor) {
+                    self.heads.insert(monitor.head_id.clone());
+                } else {
+                    self.heads.remove(&monitor.head_id);
+                }
+            }
+            WlMonitorEvent::Removed { id, .. } => {
+                self.heads.remove(id);
+            }
+            WlMonitorEvent::Disconnected => self.heads.clear(),
+            _ => {}
+        }
+    }
+}
+
+impl WlMonitorManager {
+    /// Send the events selected by `filter` to another channel as well
+    ///
+    /// Every subscriber, including the sender given to the constructor,
+    /// receives its own copy of the events it selects. A subscriber is
+    /// dropped once its receiver is gone. Sending blocks while a
+    /// subscriber's channel is full, so keep every receiver drained. To add
+    /// subscribers while `run` is executing, use [`Self::event_subscriber`].
+    ///
+    /// Managers driven by [`AsyncWlMonitorManager`] or
+    /// [`WlMonitorEventSource`] have their own event delivery and ignore
+    /// subscribers.
+    ///
+    /// [`AsyncWlMonitorManager`]: super::AsyncWlMonitorManager
+    /// [`WlMonitorEventSource`]: super::WlMonitorEventSource
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::{WlEventFilter, WlEventKinds, WlMonitorManager, action_channel};
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// let (tx, rx) = sync_channel(10);
+    /// let (action_tx, action_rx) = action_channel(10);
+    /// let (mut manager, event_queue) = WlMonitorManager::new_connection(tx, action_rx).unwrap();
+    ///
+    /// let (hotplug_tx, hotplug_rx) = sync_channel(10);
+    /// manager.subscribe(hotplug_tx, WlEventFilter::new().kinds(WlEventKinds::HOTPLUG));
+    /// ```
+    pub fn subscribe(
+        &mut self,
+        tx: SyncSender<WlMonitorEvent>,
+        filter: WlEventFilter,
+    ) {
+        self.emitter.subscribe(Subscriber::new(tx, filter));
+    }
+}
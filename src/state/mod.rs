@@ -1,17 +1,43 @@
 mod actions;
+#[cfg(feature = "async")]
+mod async_manager;
+mod broadcast;
+#[cfg(feature = "calloop")]
+mod calloop_source;
+mod channel;
+mod error;
+mod filter;
+mod reconnect;
+mod snapshot;
+mod transaction;
 
-pub use actions::{ActionKind, WlMonitorAction, WlMonitorEvent};
+pub use actions::{ActionKind, WlActionId, WlMonitorAction, WlMonitorEvent};
+#[cfg(feature = "async")]
+pub use async_manager::{AsyncWlMonitorManager, WlMonitorEventStream};
+pub use broadcast::WlEventSubscriber;
+#[cfg(feature = "calloop")]
+pub use calloop_source::{WlEventSourceSender, WlMonitorEventSource};
+pub use channel::{WlActionReceiver, WlActionSender, action_channel};
+pub use error::WlMonitorManagerError;
+pub use filter::{WlEventFilter, WlEventKinds};
+pub use reconnect::WlReconnectPolicy;
+pub use snapshot::{WlMonitorStateHandle, snapshot};
+pub use transaction::{WlHeadChange, WlTransaction};
 
 use std::{
     collections::HashMap,
-    sync::{
-        Arc,
-        mpsc::{Receiver, SyncSender},
+    os::{
+        fd::{BorrowedFd, OwnedFd},
+        unix::net::UnixStream,
     },
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, mpsc::SyncSender},
+    time::{Duration, Instant},
 };
 
 use wayland_client::{
-    Connection, Dispatch, EventQueue, Proxy, QueueHandle, backend::ObjectId,
+    Connection, Dispatch, DispatchError, EventQueue, Proxy, QueueHandle,
+    backend::{ObjectId, ReadEventsGuard, WaylandError},
     protocol::wl_registry,
 };
 use wayland_protocols_wlr::output_management::v1::client::{
@@ -23,15 +49,129 @@ use wayland_protocols_wlr::output_management::v1::client::{
 };
 
 use crate::wl_monitor::{
-    WlMonitor, WlMonitorMode, WlPosition, WlResolution, WlTransform,
+    WlAdaptiveSync, WlMonitor, WlMonitorChanges, WlMonitorMode, WlPosition,
+    WlResolution, WlTransform,
 };
 
+/// How long to wait for the compositor to answer a configuration by default
+const DEFAULT_ACTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest time the event loop sleeps before checking a plain action
+/// receiver, which cannot wake it up
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 #[derive(Debug, PartialEq)]
-enum ConfigResult {
-    Idle,
+pub(super) enum ConfigResult {
     Succeeded,
     Failed,
     Cancelled,
+    TimedOut,
+    Aborted,
+    /// The configuration can no longer be answered, e.g. because the
+    /// connection was lost
+    Error(WlMonitorManagerError),
+}
+
+/// Where the manager sends its events
+pub(super) enum Emitter {
+    Channel(Vec<filter::Subscriber>),
+    #[cfg(feature = "async")]
+    Async(async_manager::AsyncEmitter),
+    /// Buffered until the calloop source hands them to its callback
+    #[cfg(feature = "calloop")]
+    Queue(std::cell::RefCell<Vec<WlMonitorEvent>>),
+}
+
+impl Emitter {
+    /// A channel receiving every event
+    fn channel(tx: SyncSender<WlMonitorEvent>) -> Self {
+        Self::Channel(vec![filter::Subscriber::new(tx, WlEventFilter::new())])
+    }
+
+    /// Events are dropped once the receiving side is gone
+    pub(super) fn send(&mut self, event: WlMonitorEvent) {
+        match self {
+            Self::Channel(subscribers) => {
+                subscribers.retain_mut(|subscriber| subscriber.send(&event));
+            }
+            #[cfg(feature = "async")]
+            Self::Async(emitter) => emitter.send(event),
+            #[cfg(feature = "calloop")]
+            Self::Queue(queue) => queue.borrow_mut().push(event),
+        }
+    }
+
+    /// Add a channel; the other emitters deliver events on their own
+    fn subscribe(&mut self, subscriber: filter::Subscriber) {
+        match self {
+            Self::Channel(subscribers) => subscribers.push(subscriber),
+            #[cfg(feature = "async")]
+            Self::Async(_) => {}
+            #[cfg(feature = "calloop")]
+            Self::Queue(_) => {}
+        }
+    }
+}
+
+/// Where the manager's connection comes from, so it can be reopened
+pub(super) enum ConnectTarget {
+    /// `WAYLAND_DISPLAY` / `WAYLAND_SOCKET`, read again on every connect
+    Env,
+    /// A display name or absolute socket path
+    Socket(PathBuf),
+    /// A connection handed in by the caller, which cannot be reopened
+    Fixed,
+}
+
+impl ConnectTarget {
+    pub(super) fn open(&self) -> Result<Connection, WlMonitorManagerError> {
+        let connection_error = |e: &dyn std::fmt::Display| {
+            WlMonitorManagerError::ConnectionError(e.to_string())
+        };
+        match self {
+            Self::Env => {
+                Connection::connect_to_env().map_err(|e| connection_error(&e))
+            }
+            Self::Socket(socket) => {
+                let path = if socket.is_absolute() {
+                    socket.clone()
+                } else {
+                    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR")
+                        .ok_or_else(|| {
+                            connection_error(&"XDG_RUNTIME_DIR is not set")
+                        })?;
+                    Path::new(&runtime_dir).join(socket)
+                };
+                let stream = UnixStream::connect(&path)
+                    .map_err(|e| connection_error(&e))?;
+                Connection::from_socket(stream)
+                    .map_err(|e| connection_error(&e))
+            }
+            Self::Fixed => Err(connection_error(
+                &"the connection was provided by the caller and cannot be reopened",
+            )),
+        }
+    }
+}
+
+/// A configuration sent to the compositor that has not been answered yet
+pub(super) struct PendingConfig {
+    id: WlActionId,
+    config: ZwlrOutputConfigurationV1,
+    action: WlMonitorAction,
+    test_only: bool,
+    deadline: Instant,
+    serial: u32,
+    attempt: u32,
+}
+
+/// An action whose configuration was cancelled, waiting for the compositor
+/// to send a newer serial before it is rebuilt
+pub(super) struct CancelledAction {
+    id: WlActionId,
+    action: WlMonitorAction,
+    serial: u32,
+    attempt: u32,
 }
 
 /// Manages Wayland monitor/output state and communication
@@ -39,24 +179,30 @@ enum ConfigResult {
 /// This struct handles the connection to the Wayland display and provides
 /// an interface to receive monitor events and send control actions.
 pub struct WlMonitorManager {
-    _conn: Connection,
-    emitter: SyncSender<WlMonitorEvent>,
+    conn: Connection,
+    target: ConnectTarget,
+    emitter: Emitter,
     monitors: HashMap<ObjectId, WlMonitor>,
     mode_monitor: HashMap<ObjectId, ObjectId>,
-    controller: Receiver<WlMonitorAction>,
+    /// Where `run` takes actions from; the async and calloop front ends
+    /// have their own channels
+    controller: Option<WlActionReceiver>,
     zwlr_manager: Option<ZwlrOutputManagerV1>,
+    manager_global: Option<u32>,
     serial: Option<u32>,
     initialized: bool,
-    config_result: ConfigResult,
-}
-
-/// Errors that can occur when using the monitor manager
-#[derive(Debug)]
-pub enum WlMonitorManagerError {
-    /// Failed to establish Wayland connection
-    ConnectionError(String),
-    /// Error in the Wayland event queue
-    EventQueueError(String),
+    finished: bool,
+    pending: HashMap<ObjectId, PendingConfig>,
+    action_timeout: Duration,
+    cancelled: Vec<CancelledAction>,
+    max_retries: u32,
+    state: WlMonitorStateHandle,
+    previous: HashMap<ObjectId, WlMonitor>,
+    added: Vec<ObjectId>,
+    new_subscribers: Arc<Mutex<Vec<filter::Subscriber>>>,
+    /// The compositor is sending an update that has not reached its `done`
+    /// event yet
+    updating: bool,
 }
 
 impl WlMonitorManager {
@@ -67,11 +213,15 @@ impl WlMonitorManager {
     /// # Arguments
     ///
     /// * `emitter` - Channel sender for receiving monitor events
-    /// * `controller` - Channel receiver for sending control actions
+    /// * `controller` - Channel receiver for sending control actions, either
+    ///   from [`action_channel`] (wakes the event loop immediately) or a plain
+    ///   [`std::sync::mpsc::Receiver`] (polled every 50 ms)
     ///
     /// # Errors
     ///
-    /// Returns `ConnectionError` if unable to connect to the Wayland display.
+    /// Returns `ConnectionError` if unable to connect to the Wayland display,
+    /// and `ProtocolUnsupported` if the compositor does not implement
+    /// `zwlr_output_manager_v1` (e.g. GNOME or KDE).
     ///
     /// # Example
     ///
@@ -86,115 +236,442 @@ impl WlMonitorManager {
     /// ```
     pub fn new_connection(
         emitter: SyncSender<WlMonitorEvent>,
-        controller: Receiver<WlMonitorAction>,
+        controller: impl Into<WlActionReceiver>,
     ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
-        let conn = Connection::connect_to_env().map_err(|e| {
-            WlMonitorManagerError::ConnectionError(e.to_string())
-        })?;
+        Self::connect(
+            ConnectTarget::Env,
+            Emitter::channel(emitter),
+            Some(controller.into()),
+        )
+    }
 
-        let display_object = conn.display();
-        let event_queue: EventQueue<WlMonitorManager> = conn.new_event_queue();
-        let queue_handler = event_queue.handle();
-        display_object.get_registry(&queue_handler, ());
+    /// Create a monitor manager on the Wayland socket `socket`
+    ///
+    /// A relative `socket` is a display name (e.g. `wayland-1`) resolved
+    /// against `$XDG_RUNTIME_DIR`, like `WAYLAND_DISPLAY`; an absolute path
+    /// is used as-is. [`Self::run_with_reconnect`] reconnects to the same
+    /// socket.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::new_connection`].
+    pub fn connect_to_socket(
+        socket: impl AsRef<Path>,
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: impl Into<WlActionReceiver>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Self::connect(
+            ConnectTarget::Socket(socket.as_ref().to_path_buf()),
+            Emitter::channel(emitter),
+            Some(controller.into()),
+        )
+    }
+
+    /// Create a monitor manager sharing an existing Wayland connection
+    ///
+    /// The manager uses its own event queue, so the connection can keep
+    /// being used elsewhere. [`Self::run_with_reconnect`] cannot reopen a
+    /// connection it did not create and returns `ConnectionLost` instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnectionError` if the registry roundtrip fails and
+    /// `ProtocolUnsupported` if the compositor does not implement
+    /// `zwlr_output_manager_v1`.
+    pub fn from_connection(
+        conn: Connection,
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: impl Into<WlActionReceiver>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        Self::with_connection(
+            conn,
+            ConnectTarget::Fixed,
+            Emitter::channel(emitter),
+            Some(controller.into()),
+        )
+    }
 
-        let state = WlMonitorManager {
-            _conn: conn,
+    /// Create a monitor manager on an already connected Wayland socket,
+    /// e.g. one inherited through `WAYLAND_SOCKET`
+    ///
+    /// Like [`Self::from_connection`], the manager cannot reconnect.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::new_connection`].
+    pub fn from_fd(
+        fd: OwnedFd,
+        emitter: SyncSender<WlMonitorEvent>,
+        controller: impl Into<WlActionReceiver>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        let conn =
+            Connection::from_socket(UnixStream::from(fd)).map_err(|e| {
+                WlMonitorManagerError::ConnectionError(e.to_string())
+            })?;
+        Self::with_connection(
+            conn,
+            ConnectTarget::Fixed,
+            Emitter::channel(emitter),
+            Some(controller.into()),
+        )
+    }
+
+    fn connect(
+        target: ConnectTarget,
+        emitter: Emitter,
+        controller: Option<WlActionReceiver>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        let conn = target.open()?;
+        Self::with_connection(conn, target, emitter, controller)
+    }
+
+    fn with_connection(
+        conn: Connection,
+        target: ConnectTarget,
+        emitter: Emitter,
+        controller: Option<WlActionReceiver>,
+    ) -> Result<(Self, EventQueue<Self>), WlMonitorManagerError> {
+        let mut state = WlMonitorManager {
+            conn,
+            target,
             emitter,
             monitors: HashMap::new(),
             mode_monitor: HashMap::new(),
             controller,
             zwlr_manager: None,
+            manager_global: None,
             serial: None,
             initialized: false,
-            config_result: ConfigResult::Idle,
+            finished: false,
+            pending: HashMap::new(),
+            action_timeout: DEFAULT_ACTION_TIMEOUT,
+            cancelled: Vec::new(),
+            max_retries: 0,
+            state: WlMonitorStateHandle::default(),
+            previous: HashMap::new(),
+            added: Vec::new(),
+            new_subscribers: Arc::default(),
+            updating: false,
         };
+        let event_queue = state.bind_globals()?;
 
         Ok((state, event_queue))
     }
 
+    /// Create an event queue on the current connection and bind the output
+    /// manager from its registry
+    fn bind_globals(
+        &mut self,
+    ) -> Result<EventQueue<Self>, WlMonitorManagerError> {
+        let mut event_queue: EventQueue<WlMonitorManager> =
+            self.conn.new_event_queue();
+        let queue_handler = event_queue.handle();
+        self.conn.display().get_registry(&queue_handler, ());
+
+        // Wait for the registry to list its globals so a missing output
+        // manager is reported here instead of silently never sending state
+        event_queue.roundtrip(self).map_err(|e| {
+            WlMonitorManagerError::ConnectionError(e.to_string())
+        })?;
+        if self.zwlr_manager.is_none() {
+            return Err(WlMonitorManagerError::ProtocolUnsupported(
+                ZwlrOutputManagerV1::interface().name,
+            ));
+        }
+
+        Ok(event_queue)
+    }
+
+    /// Set how long to wait for the compositor to answer a configuration
+    ///
+    /// Configurations that are not answered in time are reported through
+    /// [`WlMonitorEvent::ActionFailed`]. Defaults to 5 seconds.
+    pub fn set_action_timeout(&mut self, timeout: Duration) {
+        self.action_timeout = timeout;
+    }
+
+    /// Set how many times an action is retried when the compositor cancels
+    /// its configuration
+    ///
+    /// The compositor cancels a configuration when the monitor state
+    /// changed after it was built, e.g. when a monitor is plugged in at the
+    /// same time. A cancelled action is rebuilt against the updated monitors
+    /// once the compositor sent the new state, and only reported as
+    /// [`WlMonitorManagerError::ConfigurationCancelled`] after `max_retries`
+    /// retries. Defaults to 0 (no retries).
+    pub fn set_max_retries(&mut self, max_retries: u32) {
+        self.max_retries = max_retries;
+    }
+
     /// Run the monitor manager event loop
     ///
     /// This will block and process events indefinitely, sending monitor events
     /// through the emitter channel and receiving actions from the controller channel.
     ///
+    /// Actions never block the loop: their configurations are sent to the
+    /// compositor and the results are reported when they arrive.
+    ///
     /// # Errors
     ///
-    /// Returns `EventQueueError` if there's an error in the Wayland event queue.
+    /// Returns `ConnectionLost` if the compositor closed the connection and
+    /// `EventQueueError` if there's another error in the Wayland event queue.
     ///
     /// # Note
     ///
-    /// This function runs indefinitely until an error occurs. Run it in a separate thread.
+    /// This function runs until a [`WlMonitorAction::Shutdown`] is received or
+    /// an error occurs. Run it in a separate thread.
     pub fn run(
         mut self,
         mut eq: EventQueue<Self>,
     ) -> Result<(), WlMonitorManagerError> {
-        loop {
-            eq.flush().map_err(|e| {
-                WlMonitorManagerError::EventQueueError(e.to_string())
-            })?;
+        self.event_loop(&mut eq)
+    }
 
-            let guard = eq.prepare_read().unwrap();
-            let fd = guard.connection_fd();
-            let mut poll_fd = [rustix::event::PollFd::new(
-                &fd,
-                rustix::event::PollFlags::IN,
-            )];
-            let timeout = rustix::time::Timespec {
-                tv_sec: 0,
-                tv_nsec: 50_000_000,
-            };
-            let _ = rustix::event::poll(&mut poll_fd, Some(&timeout));
-            let _ = guard.read();
-            eq.dispatch_pending(&mut self).map_err(|e| {
-                WlMonitorManagerError::EventQueueError(e.to_string())
-            })?;
-            self.flush_changed();
+    fn event_loop(
+        &mut self,
+        eq: &mut EventQueue<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        let qh = eq.handle();
+        loop {
+            eq.flush().map_err(queue_error)?;
+            poll_socket(eq, self.wake_fd(), self.poll_timeout())?;
+            eq.dispatch_pending(self).map_err(dispatch_error)?;
+            self.expire_pending();
+            self.retry_cancelled(&qh);
 
-            if let Ok(action) = self.controller.try_recv() {
-                self.handle_action(action, &mut eq)?;
+            self.clear_wake();
+            while let Some((id, action)) = self.next_action() {
+                if let WlMonitorAction::Shutdown = action {
+                    return self.shutdown(id, eq);
+                }
+                self.handle_action(id, action, &qh);
             }
+            self.add_subscribers();
         }
     }
 
-    fn flush_changed(&mut self) {
-        if !self.initialized {
-            return;
-        }
-        for monitor in self.monitors.values_mut() {
-            if monitor.changed {
-                monitor.changed = false;
-                let _ = self
-                    .emitter
-                    .send(WlMonitorEvent::Changed(Box::new(monitor.clone())));
+    /// Sleep until the next pending configuration expires, or at most
+    /// `POLL_INTERVAL` if actions cannot wake the loop
+    fn poll_timeout(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let interval = match &self.controller {
+            Some(controller) if controller.wake_fd().is_none() => {
+                Some(POLL_INTERVAL)
             }
+            _ => None,
+        };
+        self.next_deadline()
+            .map(|deadline| deadline.saturating_duration_since(now))
+            .into_iter()
+            .chain(interval)
+            .min()
+    }
+
+    /// The file descriptor that wakes the loop when an action is sent
+    fn wake_fd(&self) -> Option<BorrowedFd<'_>> {
+        self.controller.as_ref()?.wake_fd()
+    }
+
+    fn clear_wake(&self) {
+        if let Some(controller) = &self.controller {
+            controller.clear_wake();
         }
     }
 
-    fn wait_for_result(
+    /// The next action waiting in the controller, if any
+    fn next_action(&self) -> Option<(WlActionId, WlMonitorAction)> {
+        self.controller.as_ref()?.try_recv().ok()
+    }
+
+    /// When the first pending configuration expires
+    fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.deadline).min()
+    }
+
+    /// Stop the output manager, wait for the compositor to acknowledge it
+    /// and release every head and mode
+    ///
+    /// The wait is bounded by the action timeout, so a compositor that
+    /// never answers `stop` cannot keep `run` from returning.
+    fn shutdown(
         &mut self,
+        id: WlActionId,
         eq: &mut EventQueue<Self>,
     ) -> Result<(), WlMonitorManagerError> {
-        self.config_result = ConfigResult::Idle;
-        while self.config_result == ConfigResult::Idle {
-            eq.blocking_dispatch(self).map_err(|e| {
-                WlMonitorManagerError::EventQueueError(e.to_string())
-            })?;
-            self.flush_changed();
+        let deadline = self.begin_shutdown();
+        while !self.finished {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            eq.flush().map_err(queue_error)?;
+            poll_socket(eq, None, Some(remaining))?;
+            eq.dispatch_pending(self).map_err(dispatch_error)?;
         }
-        match self.config_result {
-            ConfigResult::Succeeded => Ok(()),
-            ConfigResult::Failed => {
-                Err(WlMonitorManagerError::EventQueueError(
-                    "compositor rejected the configuration".into(),
-                ))
-            }
-            ConfigResult::Cancelled => {
-                Err(WlMonitorManagerError::EventQueueError(
-                    "configuration cancelled (serial outdated)".into(),
-                ))
-            }
-            ConfigResult::Idle => unreachable!(),
+        self.finish_shutdown(id);
+        eq.flush().map_err(queue_error)
+    }
+
+    /// Fail every configuration waiting for an answer and every action
+    /// waiting to be retried with `error`
+    fn fail_in_flight(&mut self, error: WlMonitorManagerError) {
+        let pending: Vec<ObjectId> = self.pending.keys().cloned().collect();
+        for id in pending {
+            self.finish_config(&id, ConfigResult::Error(error.clone()));
         }
+        for cancelled in std::mem::take(&mut self.cancelled) {
+            self.emitter.send(WlMonitorEvent::ActionFailed {
+                id: cancelled.id,
+                action: cancelled.action.kind(),
+                error: error.clone(),
+            });
+        }
+    }
+
+    /// Abort everything in flight and ask the compositor to stop; returns
+    /// how long to wait for its `finished` event
+    fn begin_shutdown(&mut self) -> Instant {
+        let pending: Vec<ObjectId> = self.pending.keys().cloned().collect();
+        for id in pending {
+            self.finish_config(&id, ConfigResult::Aborted);
+        }
+        for cancelled in std::mem::take(&mut self.cancelled) {
+            self.emitter.send(WlMonitorEvent::ActionFailed {
+                id: cancelled.id,
+                action: cancelled.action.kind(),
+                error: WlMonitorManagerError::ConfigurationAborted,
+            });
+        }
+
+        match &self.zwlr_manager {
+            Some(manager) => manager.stop(),
+            None => self.finished = true,
+        }
+        Instant::now() + self.action_timeout
+    }
+
+    /// Release every head and mode, whether or not the compositor
+    /// acknowledged the `stop` request, and abort the actions still queued
+    fn finish_shutdown(&mut self, id: WlActionId) {
+        for monitor in self.monitors.values() {
+            for mode in &monitor.modes {
+                if mode.proxy.version() >= 3 {
+                    mode.proxy.release();
+                }
+            }
+            if monitor.head.version() >= 3 {
+                monitor.head.release();
+            }
+        }
+        self.monitors.clear();
+        self.mode_monitor.clear();
+        self.publish_state();
+        self.emitter.send(WlMonitorEvent::ActionSucceeded { id });
+
+        // Actions queued behind the shutdown still get a result
+        while let Some((id, action)) = self.next_action() {
+            self.abort_action(id, &action);
+        }
+    }
+
+    /// Answer an action that arrived too late to be handled
+    fn abort_action(&mut self, id: WlActionId, action: &WlMonitorAction) {
+        self.emitter.send(WlMonitorEvent::ActionFailed {
+            id,
+            action: action.kind(),
+            error: WlMonitorManagerError::ConfigurationAborted,
+        });
+    }
+
+    /// Report everything that changed since the previous `done` event, so
+    /// consumers never see a monitor with only part of an update applied
+    fn flush_changed(&mut self, serial: u32) {
+        for monitor in self.monitors.values_mut() {
+            if monitor.changes.is_empty() {
+                continue;
+            }
+            let changes = std::mem::take(&mut monitor.changes);
+            let previous = self
+                .previous
+                .remove(&monitor.head_id)
+                .unwrap_or_else(|| monitor.clone());
+            self.emitter.send(WlMonitorEvent::Changed {
+                monitor: Box::new(monitor.clone()),
+                changes,
+                previous: Box::new(previous),
+                serial,
+            });
+        }
+        self.previous.clear();
+    }
+}
+
+/// A closed socket means the compositor went away; anything else is a
+/// protocol or dispatch error
+fn queue_error(e: WaylandError) -> WlMonitorManagerError {
+    match e {
+        WaylandError::Io(e) => {
+            WlMonitorManagerError::ConnectionLost(e.to_string())
+        }
+        e => WlMonitorManagerError::EventQueueError(e.to_string()),
+    }
+}
+
+/// Read what the socket holds into the event queue; returns `false` if
+/// there was nothing to read yet
+fn read_events(guard: ReadEventsGuard) -> Result<bool, WlMonitorManagerError> {
+    match guard.read() {
+        Err(WaylandError::Io(e))
+            if e.kind() == std::io::ErrorKind::WouldBlock =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(queue_error(e)),
+        Ok(_) => Ok(true),
+    }
+}
+
+/// Wait until the socket or `wake_fd` is readable or `timeout` passed, then
+/// read what arrived
+///
+/// Returns right away when another user of a shared connection already
+/// queued events for us, which only need to be dispatched.
+fn poll_socket(
+    eq: &EventQueue<WlMonitorManager>,
+    wake_fd: Option<BorrowedFd<'_>>,
+    timeout: Option<Duration>,
+) -> Result<(), WlMonitorManagerError> {
+    let Some(guard) = eq.prepare_read() else {
+        return Ok(());
+    };
+    let fd = guard.connection_fd();
+    let mut poll_fds = vec![rustix::event::PollFd::new(
+        &fd,
+        rustix::event::PollFlags::IN,
+    )];
+    if let Some(wake_fd) = wake_fd {
+        poll_fds.push(rustix::event::PollFd::from_borrowed_fd(
+            wake_fd,
+            rustix::event::PollFlags::IN,
+        ));
+    }
+    let timeout = timeout.map(timespec);
+    let _ = rustix::event::poll(&mut poll_fds, timeout.as_ref());
+    drop(poll_fds);
+    read_events(guard).map(|_| ())
+}
+
+fn timespec(duration: Duration) -> rustix::time::Timespec {
+    rustix::time::Timespec {
+        tv_sec: duration.as_secs() as _,
+        tv_nsec: duration.subsec_nanos() as _,
+    }
+}
+
+fn dispatch_error(e: DispatchError) -> WlMonitorManagerError {
+    match e {
+        DispatchError::Backend(e) => queue_error(e),
+        e => WlMonitorManagerError::EventQueueError(e.to_string()),
     }
 }
 
@@ -207,20 +684,43 @@ impl Dispatch<wl_registry::WlRegistry, ()> for WlMonitorManager {
         _: &Connection,
         qh: &QueueHandle<Self>,
     ) {
-        if let wl_registry::Event::Global {
-            name,
-            interface,
-            version,
-        } = event
-            && interface == ZwlrOutputManagerV1::interface().name
-        {
-            let bound = registry.bind::<ZwlrOutputManagerV1, _, _>(
+        match event {
+            wl_registry::Event::Global {
                 name,
+                interface,
                 version,
-                qh,
-                (),
-            );
-            state.zwlr_manager = Some(bound);
+            } if interface == ZwlrOutputManagerV1::interface().name => {
+                // Never bind a newer version than the bindings know about
+                let version =
+                    version.min(ZwlrOutputManagerV1::interface().version);
+                let bound = registry.bind::<ZwlrOutputManagerV1, _, _>(
+                    name,
+                    version,
+                    qh,
+                    (),
+                );
+                state.zwlr_manager = Some(bound);
+                state.manager_global = Some(name);
+            }
+            wl_registry::Event::GlobalRemove { name }
+                if state.manager_global == Some(name) =>
+            {
+                state.zwlr_manager = None;
+                state.manager_global = None;
+                state.fail_in_flight(
+                    WlMonitorManagerError::ProtocolUnsupported(
+                        ZwlrOutputManagerV1::interface().name,
+                    ),
+                );
+                state.monitors.clear();
+                state.mode_monitor.clear();
+                state.previous.clear();
+                state.added.clear();
+                state.updating = false;
+                state.publish_state();
+                state.emitter.send(WlMonitorEvent::OutputManagerRemoved);
+            }
+            _ => {}
         }
     }
 }
@@ -236,6 +736,17 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
     ) {
         match event {
             zwlr_output_manager_v1::Event::Head { head } => {
+                state.updating = true;
+                // Heads only send `adaptive_sync` from version 4 on
+                let adaptive_sync = if head.version() >= 4 {
+                    WlAdaptiveSync::Disabled
+                } else {
+                    WlAdaptiveSync::Unsupported
+                };
+                if state.initialized {
+                    // Reported as `Added` once its properties are complete
+                    state.added.push(head.id());
+                }
                 state.monitors.insert(
                     head.id(),
                     WlMonitor {
@@ -245,6 +756,8 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
                         make: String::new(),
                         model: String::new(),
                         serial_number: String::new(),
+                        physical_width_mm: 0,
+                        physical_height_mm: 0,
                         modes: Vec::new(),
                         resolution: WlResolution::default(),
                         position: WlPosition::default(),
@@ -252,22 +765,44 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for WlMonitorManager {
                         enabled: false,
                         current_mode: None,
                         transform: WlTransform::Normal,
+                        adaptive_sync,
                         head,
-                        changed: false,
+                        changes: WlMonitorChanges::empty(),
                         last_mode: None,
                     },
                 );
             }
+            zwlr_output_manager_v1::Event::Finished => {
+                state.zwlr_manager = None;
+                state.finished = true;
+            }
             zwlr_output_manager_v1::Event::Done { serial } => {
                 state.serial = Some(serial);
+                let mut added = Vec::new();
+                for head_id in std::mem::take(&mut state.added) {
+                    if let Some(monitor) = state.monitors.get_mut(&head_id) {
+                        monitor.changes = WlMonitorChanges::empty();
+                        state.previous.remove(&head_id);
+                        added.push(monitor.clone());
+                    }
+                }
+                if state.initialized {
+                    state.flush_changed(serial);
+                }
+                for monitor in added {
+                    state
+                        .emitter
+                        .send(WlMonitorEvent::Added(Box::new(monitor)));
+                }
+                state.publish_state();
                 if !state.initialized {
                     state.initialized = true;
 
                     let monitors = state.monitors.values().cloned().collect();
-                    let _ = state
-                        .emitter
-                        .send(WlMonitorEvent::InitialState(monitors));
+                    state.emitter.send(WlMonitorEvent::InitialState(monitors));
                 }
+                state.updating = false;
+                state.add_subscribers();
             }
             _ => {}
         }
@@ -295,11 +830,20 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for WlMonitorManager {
         _: &QueueHandle<Self>,
     ) {
         let head_id = head.id();
+        state.updating = true;
 
         if let zwlr_output_head_v1::Event::Finished = &event {
             if let Some(monitor) = state.monitors.remove(&head_id) {
                 state.mode_monitor.retain(|_, head| *head != head_id);
-                let _ = state.emitter.send(WlMonitorEvent::Removed {
+                state.previous.remove(&head_id);
+                // Never reported as added, so not reported as removed either
+                if let Some(i) =
+                    state.added.iter().position(|id| *id == head_id)
+                {
+                    state.added.remove(i);
+                    return;
+                }
+                state.emitter.send(WlMonitorEvent::Removed {
                     id: monitor.head_id,
                     name: monitor.name,
                 });
@@ -310,6 +854,12 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for WlMonitorManager {
         let Some(monitor) = state.monitors.get_mut(&head_id) else {
             return;
         };
+        if state.initialized {
+            state
+                .previous
+                .entry(head_id.clone())
+                .or_insert_with(|| monitor.clone());
+        }
 
         if let zwlr_output_head_v1::Event::Mode { mode } = &event {
             state.mode_monitor.insert(mode.id(), head_id);
@@ -317,53 +867,79 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for WlMonitorManager {
                 mode_id: mode.id(),
                 head_id: monitor.head_id.clone(),
                 refresh_rate: 0,
+                refresh_mhz: 0,
                 resolution: WlResolution::default(),
                 preferred: false,
                 is_current: false,
                 proxy: mode.clone(),
             });
+            if state.initialized {
+                monitor.changes |= WlMonitorChanges::MODES_LIST;
+            }
             return;
         }
 
-        match event {
+        let changes = match event {
             zwlr_output_head_v1::Event::Name { name } => {
                 monitor.name = name;
+                WlMonitorChanges::IDENTITY
             }
             zwlr_output_head_v1::Event::Description { description } => {
                 monitor.description = description;
+                WlMonitorChanges::DESCRIPTION
             }
             zwlr_output_head_v1::Event::Make { make } => {
                 monitor.make = make;
+                WlMonitorChanges::IDENTITY
             }
             zwlr_output_head_v1::Event::Model { model } => {
                 monitor.model = model;
+                WlMonitorChanges::IDENTITY
             }
             zwlr_output_head_v1::Event::SerialNumber { serial_number } => {
                 monitor.serial_number = serial_number;
+                WlMonitorChanges::IDENTITY
+            }
+            zwlr_output_head_v1::Event::PhysicalSize { width, height } => {
+                monitor.physical_width_mm = width;
+                monitor.physical_height_mm = height;
+                WlMonitorChanges::IDENTITY
             }
             zwlr_output_head_v1::Event::Enabled { enabled } => {
-                monitor.enabled = enabled != 0
+                monitor.enabled = enabled != 0;
+                WlMonitorChanges::ENABLED
             }
             zwlr_output_head_v1::Event::CurrentMode { mode } => {
                 monitor.current_mode = Some(mode.clone());
                 for m in &mut monitor.modes {
                     m.is_current = m.mode_id == mode.id();
+                    if m.is_current {
+                        monitor.resolution = m.resolution.clone();
+                    }
                 }
+                WlMonitorChanges::MODE
             }
             zwlr_output_head_v1::Event::Position { x, y } => {
                 monitor.position = WlPosition { x, y };
+                WlMonitorChanges::POSITION
             }
             zwlr_output_head_v1::Event::Scale { scale } => {
                 monitor.scale = scale;
+                WlMonitorChanges::SCALE
             }
             zwlr_output_head_v1::Event::Transform { transform } => {
                 monitor.transform = WlTransform::from_wayland(transform);
+                WlMonitorChanges::TRANSFORM
             }
-            _ => {}
-        }
+            zwlr_output_head_v1::Event::AdaptiveSync { state: sync } => {
+                monitor.adaptive_sync = WlAdaptiveSync::from_wayland(sync);
+                WlMonitorChanges::ADAPTIVE_SYNC
+            }
+            _ => WlMonitorChanges::empty(),
+        };
 
         if state.initialized {
-            monitor.changed = true;
+            monitor.changes |= changes;
         }
     }
 
@@ -389,28 +965,85 @@ impl Dispatch<ZwlrOutputModeV1, ()> for WlMonitorManager {
         _: &QueueHandle<Self>,
     ) {
         let mode_id = mode_obj.id();
+        state.updating = true;
+
+        if let zwlr_output_mode_v1::Event::Finished = event {
+            if let Some(monitor_id) = state.mode_monitor.remove(&mode_id)
+                && let Some(monitor) = state.monitors.get_mut(&monitor_id)
+            {
+                if state.initialized {
+                    state
+                        .previous
+                        .entry(monitor_id)
+                        .or_insert_with(|| monitor.clone());
+                }
+                monitor.modes.retain(|m| m.mode_id != mode_id);
+                if monitor.last_mode.as_ref() == Some(&mode_id) {
+                    monitor.last_mode = None;
+                }
+                if monitor
+                    .current_mode
+                    .as_ref()
+                    .is_some_and(|m| m.id() == mode_id)
+                {
+                    monitor.current_mode = None;
+                    monitor.resolution = WlResolution::default();
+                    if state.initialized {
+                        monitor.changes |= WlMonitorChanges::MODE;
+                    }
+                }
+                if state.initialized {
+                    monitor.changes |= WlMonitorChanges::MODES_LIST;
+                }
+            }
+            if mode_obj.version() >= 3 {
+                mode_obj.release();
+            }
+            return;
+        }
+
         let Some(monitor_id) = state.mode_monitor.get(&mode_id) else {
             return;
         };
         let Some(monitor) = state.monitors.get_mut(monitor_id) else {
             return;
         };
+        if state.initialized {
+            state
+                .previous
+                .entry(monitor_id.clone())
+                .or_insert_with(|| monitor.clone());
+        }
         let Some(mode) =
             monitor.modes.iter_mut().find(|m| m.mode_id == mode_id)
         else {
             return;
         };
+        let is_current = mode.is_current;
         match event {
             zwlr_output_mode_v1::Event::Size { width, height } => {
                 mode.resolution = WlResolution { width, height };
+                // The size may arrive after the head's `current_mode` event
+                if is_current {
+                    monitor.resolution = WlResolution { width, height };
+                }
             }
             zwlr_output_mode_v1::Event::Refresh { refresh } => {
                 mode.refresh_rate = refresh / 1000;
+                mode.refresh_mhz = refresh;
             }
             zwlr_output_mode_v1::Event::Preferred => {
                 mode.preferred = true;
             }
-            _ => {}
+            _ => return,
+        }
+
+        if state.initialized {
+            monitor.changes |= if is_current {
+                WlMonitorChanges::MODE
+            } else {
+                WlMonitorChanges::MODES_LIST
+            };
         }
     }
 }
@@ -418,24 +1051,23 @@ impl Dispatch<ZwlrOutputModeV1, ()> for WlMonitorManager {
 impl Dispatch<ZwlrOutputConfigurationV1, ()> for WlMonitorManager {
     fn event(
         state: &mut Self,
-        _: &ZwlrOutputConfigurationV1,
+        config: &ZwlrOutputConfigurationV1,
         event: zwlr_output_configuration_v1::Event,
         _: &(),
         _: &Connection,
         _: &QueueHandle<Self>,
     ) {
-        match event {
+        let result = match event {
             zwlr_output_configuration_v1::Event::Succeeded => {
-                state.config_result = ConfigResult::Succeeded;
-            }
-            zwlr_output_configuration_v1::Event::Failed => {
-                state.config_result = ConfigResult::Failed;
+                ConfigResult::Succeeded
             }
+            zwlr_output_configuration_v1::Event::Failed => ConfigResult::Failed,
             zwlr_output_configuration_v1::Event::Cancelled => {
-                state.config_result = ConfigResult::Cancelled;
+                ConfigResult::Cancelled
             }
-            _ => {}
-        }
+            _ => return,
+        };
+        state.finish_config(&config.id(), result);
     }
 }
 
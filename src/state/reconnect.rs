@@ -0,0 +1,177 @@
+use std::time::{Duration, Instant};
+
+use wayland_client::EventQueue;
+
+use super::{
+    ConnectTarget, POLL_INTERVAL, WlMonitorAction, WlMonitorEvent,
+    WlMonitorManager, WlMonitorManagerError, timespec,
+};
+
+/// How [`WlMonitorManager::run_with_reconnect`] waits between attempts to
+/// reconnect to the compositor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WlReconnectPolicy {
+    /// Delay before the first attempt
+    pub initial_delay: Duration,
+    /// Upper bound for the delay, which doubles after every failed attempt
+    pub max_delay: Duration,
+    /// Give up after this many failed attempts in a row (`None` retries
+    /// forever)
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for WlReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            max_attempts: None,
+        }
+    }
+}
+
+impl WlMonitorManager {
+    /// Run the event loop like [`Self::run`], reconnecting whenever the
+    /// connection to the compositor is lost
+    ///
+    /// When the compositor goes away (crash, restart, logout), every
+    /// unanswered action fails with `ConnectionLost`, the known monitors are
+    /// dropped and [`WlMonitorEvent::Disconnected`] is sent. The manager then
+    /// reconnects with exponential backoff, to `WAYLAND_DISPLAY` (read again
+    /// on every attempt) or the socket given to [`Self::connect_to_socket`],
+    /// sends [`WlMonitorEvent::Reconnected`] and a fresh
+    /// [`WlMonitorEvent::InitialState`]. Actions received while disconnected
+    /// fail with `NotReady`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ConnectionLost` right away for managers created with
+    /// [`Self::from_connection`] or [`Self::from_fd`], which cannot be
+    /// reopened. Otherwise returns the last connection error once
+    /// `policy.max_attempts` attempts in a row failed, and `EventQueueError`
+    /// for errors other than a lost connection.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use wlx_monitors::{WlMonitorManager, WlReconnectPolicy, action_channel};
+    /// use std::sync::mpsc::sync_channel;
+    ///
+    /// let (tx, rx) = sync_channel(10);
+    /// let (action_tx, action_rx) = action_channel(10);
+    ///
+    /// let (manager, event_queue) = WlMonitorManager::new_connection(tx, action_rx).unwrap();
+    /// std::thread::spawn(move || {
+    ///     manager.run_with_reconnect(event_queue, WlReconnectPolicy::default())
+    /// });
+    /// ```
+    pub fn run_with_reconnect(
+        mut self,
+        mut eq: EventQueue<Self>,
+        policy: WlReconnectPolicy,
+    ) -> Result<(), WlMonitorManagerError> {
+        loop {
+            let reason = match self.event_loop(&mut eq) {
+                Err(WlMonitorManagerError::ConnectionLost(reason)) => reason,
+                result => return result,
+            };
+            if let ConnectTarget::Fixed = self.target {
+                return Err(WlMonitorManagerError::ConnectionLost(reason));
+            }
+            self.disconnect(reason);
+
+            let Some(new_eq) = self.reconnect(&policy)? else {
+                return Ok(());
+            };
+            eq = new_eq;
+            self.emitter.send(WlMonitorEvent::Reconnected);
+        }
+    }
+
+    /// Fail everything in flight and forget the state of the old connection
+    fn disconnect(&mut self, reason: String) {
+        self.fail_in_flight(WlMonitorManagerError::ConnectionLost(reason));
+
+        self.monitors.clear();
+        self.mode_monitor.clear();
+        self.previous.clear();
+        self.added.clear();
+        self.updating = false;
+        self.publish_state();
+        self.zwlr_manager = None;
+        self.manager_global = None;
+        self.serial = None;
+        self.initialized = false;
+        self.finished = false;
+        self.emitter.send(WlMonitorEvent::Disconnected);
+    }
+
+    /// Reconnect with backoff; returns `None` if a `Shutdown` action
+    /// arrived while waiting
+    fn reconnect(
+        &mut self,
+        policy: &WlReconnectPolicy,
+    ) -> Result<Option<EventQueue<Self>>, WlMonitorManagerError> {
+        let mut delay = policy.initial_delay;
+        let mut attempts = 0;
+        loop {
+            if self.wait_disconnected(delay) {
+                return Ok(None);
+            }
+
+            self.zwlr_manager = None;
+            self.manager_global = None;
+            let connected = self.target.open().and_then(|conn| {
+                self.conn = conn;
+                self.bind_globals()
+            });
+            match connected {
+                Ok(eq) => return Ok(Some(eq)),
+                Err(e) => {
+                    attempts += 1;
+                    if policy.max_attempts.is_some_and(|max| attempts >= max) {
+                        return Err(e);
+                    }
+                    delay = (delay * 2).min(policy.max_delay);
+                }
+            }
+        }
+    }
+
+    /// Sleep for `delay` while answering actions; returns `true` if a
+    /// `Shutdown` action arrived
+    fn wait_disconnected(&mut self, delay: Duration) -> bool {
+        let deadline = Instant::now() + delay;
+        loop {
+            self.clear_wake();
+            while let Some((id, action)) = self.next_action() {
+                if let WlMonitorAction::Shutdown = action {
+                    self.emitter.send(WlMonitorEvent::ActionSucceeded { id });
+                    return true;
+                }
+                self.emitter.send(WlMonitorEvent::ActionFailed {
+                    id,
+                    action: action.kind(),
+                    error: WlMonitorManagerError::NotReady,
+                });
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            match self.wake_fd() {
+                Some(wake_fd) => {
+                    let mut poll_fds =
+                        [rustix::event::PollFd::from_borrowed_fd(
+                            wake_fd,
+                            rustix::event::PollFlags::IN,
+                        )];
+                    let timeout = timespec(remaining);
+                    let _ = rustix::event::poll(&mut poll_fds, Some(&timeout));
+                }
+                None => std::thread::sleep(remaining.min(POLL_INTERVAL)),
+            }
+        }
+    }
+}
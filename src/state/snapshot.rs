@@ -0,0 +1,84 @@
+use std::sync::{Arc, RwLock};
+
+use crate::wl_monitor::WlMonitor;
+
+use super::{
+    ConnectTarget, Emitter, WlMonitorManager, WlMonitorManagerError,
+    dispatch_error,
+};
+
+/// Read the current monitors once, without running an event loop
+///
+/// Connects to `WAYLAND_DISPLAY`, waits until the compositor sent the
+/// complete monitor state and returns it sorted by name.
+///
+/// # Errors
+///
+/// Same as [`WlMonitorManager::new_connection`], plus `ConnectionLost` or
+/// `EventQueueError` if the connection fails while waiting.
+///
+/// # Example
+///
+/// ```no_run
+/// for monitor in wlx_monitors::snapshot().unwrap() {
+///     println!("{}: {}x{}", monitor.name, monitor.resolution.width, monitor.resolution.height);
+/// }
+/// ```
+pub fn snapshot() -> Result<Vec<WlMonitor>, WlMonitorManagerError> {
+    // Nobody listens: the InitialState event is dropped
+    let (mut manager, mut eq) = WlMonitorManager::connect(
+        ConnectTarget::Env,
+        Emitter::Channel(Vec::new()),
+        None,
+    )?;
+
+    while !manager.initialized {
+        eq.blocking_dispatch(&mut manager).map_err(dispatch_error)?;
+    }
+
+    Ok(manager.state.monitors())
+}
+
+/// Cheaply clonable, read-only view of a manager's current monitors
+///
+/// Obtained from [`WlMonitorManager::state_handle`] before calling `run`;
+/// any thread can then read the monitors as of the last complete update
+/// from the compositor (its `done` event), without tracking events itself.
+/// Empty until the initial state arrives.
+#[derive(Debug, Clone, Default)]
+pub struct WlMonitorStateHandle {
+    monitors: Arc<RwLock<Vec<WlMonitor>>>,
+}
+
+impl WlMonitorStateHandle {
+    /// All current monitors, sorted by name
+    pub fn monitors(&self) -> Vec<WlMonitor> {
+        self.monitors.read().unwrap().clone()
+    }
+
+    /// The current state of the monitor with the given name
+    pub fn monitor(&self, name: &str) -> Option<WlMonitor> {
+        self.monitors
+            .read()
+            .unwrap()
+            .iter()
+            .find(|m| m.name == name)
+            .cloned()
+    }
+}
+
+impl WlMonitorManager {
+    /// Get a handle to read the current monitors from any thread while
+    /// `run` is executing
+    pub fn state_handle(&self) -> WlMonitorStateHandle {
+        self.state.clone()
+    }
+
+    /// Copy the monitors into the shared state after each complete update
+    pub(super) fn publish_state(&self) {
+        let mut monitors: Vec<WlMonitor> =
+            self.monitors.values().cloned().collect();
+        monitors.sort_by(|a, b| a.name.cmp(&b.name));
+        *self.state.monitors.write().unwrap() = monitors;
+    }
+}
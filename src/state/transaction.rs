@@ -0,0 +1,277 @@
+use wayland_client::{Proxy, QueueHandle};
+use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_v1::ZwlrOutputConfigurationV1,
+    zwlr_output_mode_v1::ZwlrOutputModeV1,
+};
+
+use crate::wl_monitor::{
+    WlAdaptiveSync, WlMonitor, WlRefreshRate, WlTransform,
+};
+
+use super::{WlMonitorManager, WlMonitorManagerError};
+
+/// Changes to apply to a single monitor as part of a [`WlTransaction`]
+///
+/// Every property left as `None` keeps the monitor's current value. A
+/// monitor that is or becomes disabled cannot have other properties set.
+#[derive(Debug, Clone)]
+pub struct WlHeadChange {
+    /// Name of the monitor to configure (e.g., "DP-1")
+    pub name: String,
+    /// Enable (`true`) or disable (`false`) the monitor
+    pub enabled: Option<bool>,
+    /// Desired mode: (width, height, refresh_rate)
+    pub mode: Option<(i32, i32, WlRefreshRate)>,
+    /// Desired position in the global coordinate space: (x, y)
+    pub position: Option<(i32, i32)>,
+    /// Desired scale factor (must be > 0)
+    pub scale: Option<f64>,
+    /// Desired transform (rotation/flip)
+    pub transform: Option<WlTransform>,
+    /// Enable (`true`) or disable (`false`) adaptive sync (VRR)
+    pub adaptive_sync: Option<bool>,
+}
+
+impl WlHeadChange {
+    /// Create an empty change set for the monitor with the given name
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            enabled: None,
+            mode: None,
+            position: None,
+            scale: None,
+            transform: None,
+            adaptive_sync: None,
+        }
+    }
+
+    /// Enable the monitor
+    pub fn enable(mut self) -> Self {
+        self.enabled = Some(true);
+        self
+    }
+
+    /// Disable the monitor
+    pub fn disable(mut self) -> Self {
+        self.enabled = Some(false);
+        self
+    }
+
+    /// Switch to the mode matching `width`x`height`@`refresh_rate`
+    ///
+    /// A plain `i32` is matched as whole Hz.
+    pub fn mode(
+        mut self,
+        width: i32,
+        height: i32,
+        refresh_rate: impl Into<WlRefreshRate>,
+    ) -> Self {
+        self.mode = Some((width, height, refresh_rate.into()));
+        self
+    }
+
+    /// Move the monitor to `(x, y)`
+    pub fn position(mut self, x: i32, y: i32) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Set the scale factor
+    pub fn scale(mut self, scale: f64) -> Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    /// Set the transform
+    pub fn transform(mut self, transform: WlTransform) -> Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    /// Enable or disable adaptive sync (VRR)
+    pub fn adaptive_sync(mut self, enabled: bool) -> Self {
+        self.adaptive_sync = Some(enabled);
+        self
+    }
+}
+
+/// A set of per-monitor changes applied atomically as one configuration
+///
+/// Monitors not mentioned in the transaction keep their current state.
+///
+/// # Example
+///
+/// ```
+/// use wlx_monitors::{WlHeadChange, WlMonitorAction, WlTransaction};
+///
+/// let transaction = WlTransaction::new()
+///     .head(WlHeadChange::new("DP-1").mode(2560, 1440, 144).position(0, 0))
+///     .head(WlHeadChange::new("HDMI-A-1").position(2560, 0).scale(1.5));
+///
+/// let action = WlMonitorAction::Apply(transaction);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WlTransaction {
+    /// Changes for each monitor in the transaction
+    pub heads: Vec<WlHeadChange>,
+}
+
+impl WlTransaction {
+    /// Create an empty transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the changes for one monitor to the transaction
+    pub fn head(mut self, change: WlHeadChange) -> Self {
+        self.heads.push(change);
+        self
+    }
+}
+
+impl WlMonitorManager {
+    /// Validate the whole transaction before adding anything to `config`,
+    /// so a rejected transaction never reaches the compositor.
+    pub(super) fn configure_apply(
+        &mut self,
+        config: &ZwlrOutputConfigurationV1,
+        transaction: &WlTransaction,
+        qh: &QueueHandle<Self>,
+    ) -> Result<(), WlMonitorManagerError> {
+        for (i, change) in transaction.heads.iter().enumerate() {
+            if transaction.heads[..i].iter().any(|c| c.name == change.name) {
+                return Err(WlMonitorManagerError::DuplicateMonitor(
+                    change.name.clone(),
+                ));
+            }
+
+            let monitor = self.find_monitor(&change.name)?;
+
+            // A disabled head takes no properties, so they would be dropped
+            if !change.enabled.unwrap_or(monitor.enabled)
+                && (change.mode.is_some()
+                    || change.position.is_some()
+                    || change.scale.is_some()
+                    || change.transform.is_some()
+                    || change.adaptive_sync.is_some())
+            {
+                return Err(WlMonitorManagerError::MonitorDisabled(
+                    change.name.clone(),
+                ));
+            }
+
+            if let Some(scale) = change.scale
+                && (!scale.is_finite() || scale <= 0.0)
+            {
+                return Err(WlMonitorManagerError::InvalidScale(scale));
+            }
+
+            if change.adaptive_sync.is_some()
+                && monitor.adaptive_sync == WlAdaptiveSync::Unsupported
+            {
+                return Err(WlMonitorManagerError::AdaptiveSyncUnsupported(
+                    change.name.clone(),
+                ));
+            }
+
+            if change.enabled.unwrap_or(monitor.enabled)
+                && Self::resolve_change_mode(monitor, change).is_none()
+            {
+                return Err(match change.mode {
+                    Some((width, height, refresh_rate)) => {
+                        WlMonitorManagerError::NoMatchingMode {
+                            name: change.name.clone(),
+                            width,
+                            height,
+                            refresh_rate,
+                        }
+                    }
+                    None => {
+                        WlMonitorManagerError::NoValidMode(change.name.clone())
+                    }
+                });
+            }
+        }
+
+        // Remember the active mode of monitors being turned off, like Toggle
+        for change in &transaction.heads {
+            if change.enabled != Some(false) {
+                continue;
+            }
+            if let Some(monitor) =
+                self.monitors.values_mut().find(|m| m.name == change.name)
+                && monitor.enabled
+                && let Some(current_mode) = &monitor.current_mode
+            {
+                monitor.last_mode = Some(current_mode.id());
+            }
+        }
+
+        for monitor in self.monitors.values() {
+            let Some(change) =
+                transaction.heads.iter().find(|c| c.name == monitor.name)
+            else {
+                Self::preserve_head(config, monitor, qh);
+                continue;
+            };
+
+            if !change.enabled.unwrap_or(monitor.enabled) {
+                config.disable_head(&monitor.head);
+                continue;
+            }
+
+            let Some(mode) = Self::resolve_change_mode(monitor, change) else {
+                continue;
+            };
+            let (x, y) = change
+                .position
+                .unwrap_or((monitor.position.x, monitor.position.y));
+
+            let config_head = config.enable_head(&monitor.head, qh, ());
+            config_head.set_mode(&mode);
+            config_head.set_position(x, y);
+            config_head.set_transform(
+                change.transform.unwrap_or(monitor.transform).to_wayland(),
+            );
+            config_head.set_scale(change.scale.unwrap_or(monitor.scale));
+            Self::set_adaptive_sync(
+                &config_head,
+                match change.adaptive_sync {
+                    Some(true) => WlAdaptiveSync::Enabled,
+                    Some(false) => WlAdaptiveSync::Disabled,
+                    None => monitor.adaptive_sync,
+                },
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Mode priority: requested mode > current mode > last mode >
+    /// preferred mode > first available
+    fn resolve_change_mode(
+        monitor: &WlMonitor,
+        change: &WlHeadChange,
+    ) -> Option<ZwlrOutputModeV1> {
+        if let Some((width, height, refresh_rate)) = change.mode {
+            return monitor
+                .find_mode(width, height, refresh_rate)
+                .map(|m| m.proxy.clone());
+        }
+
+        if monitor.enabled
+            && let Some(current_mode) = &monitor.current_mode
+        {
+            return Some(current_mode.clone());
+        }
+
+        monitor
+            .last_mode
+            .as_ref()
+            .and_then(|last| monitor.modes.iter().find(|m| m.mode_id == *last))
+            .or_else(|| monitor.modes.iter().find(|m| m.preferred))
+            .or_else(|| monitor.modes.first())
+            .map(|m| m.proxy.clone())
+    }
+}
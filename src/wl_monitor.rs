@@ -2,7 +2,7 @@ use wayland_client::{
     WEnum, backend::ObjectId, protocol::wl_output::Transform,
 };
 use wayland_protocols_wlr::output_management::v1::client::{
-    zwlr_output_head_v1::ZwlrOutputHeadV1,
+    zwlr_output_head_v1::{AdaptiveSyncState, ZwlrOutputHeadV1},
     zwlr_output_mode_v1::ZwlrOutputModeV1,
 };
 
@@ -48,6 +48,35 @@ impl WlTransform {
     }
 }
 
+/// Adaptive sync (VRR) state of a monitor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WlAdaptiveSync {
+    /// The compositor does not support controlling adaptive sync
+    /// (`zwlr_output_head_v1` version < 4)
+    Unsupported,
+    /// Adaptive sync is disabled
+    Disabled,
+    /// Adaptive sync is enabled
+    Enabled,
+}
+
+impl WlAdaptiveSync {
+    pub(crate) fn from_wayland(state: WEnum<AdaptiveSyncState>) -> Self {
+        match state {
+            WEnum::Value(AdaptiveSyncState::Enabled) => Self::Enabled,
+            _ => Self::Disabled,
+        }
+    }
+
+    pub(crate) fn to_wayland(self) -> Option<AdaptiveSyncState> {
+        match self {
+            Self::Unsupported => None,
+            Self::Disabled => Some(AdaptiveSyncState::Disabled),
+            Self::Enabled => Some(AdaptiveSyncState::Enabled),
+        }
+    }
+}
+
 /// Represents the resolution of a monitor mode
 #[derive(Default, Clone, Debug)]
 pub struct WlResolution {
@@ -73,8 +102,10 @@ pub struct WlMonitorMode {
     pub mode_id: ObjectId,
     /// Internal Wayland object ID for the monitor head this mode belongs to
     pub head_id: ObjectId,
-    /// Refresh rate in Hz
+    /// Refresh rate in Hz, rounded down (use `refresh_mhz` for precision)
     pub refresh_rate: i32,
+    /// Exact refresh rate in mHz as reported by the compositor
+    pub refresh_mhz: i32,
     /// Screen resolution
     pub resolution: WlResolution,
     /// Whether this is the preferred mode for the monitor
@@ -91,6 +122,7 @@ impl std::fmt::Debug for WlMonitorMode {
             .field("mode_id", &self.mode_id)
             .field("head_id", &self.head_id)
             .field("refresh_rate", &self.refresh_rate)
+            .field("refresh_mhz", &self.refresh_mhz)
             .field("resolution", &self.resolution)
             .field("preferred", &self.preferred)
             .field("is_current", &self.is_current)
@@ -98,6 +130,99 @@ impl std::fmt::Debug for WlMonitorMode {
     }
 }
 
+impl WlMonitorMode {
+    /// Refresh rate in Hz (e.g., 59.94)
+    pub fn refresh_hz(&self) -> f64 {
+        f64::from(self.refresh_mhz) / 1000.0
+    }
+}
+
+/// How a requested refresh rate is matched against a monitor's modes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WlRefreshRate {
+    /// Whole Hz, compared against `WlMonitorMode::refresh_rate`
+    Hz(i32),
+    /// Exact refresh rate in mHz
+    Exact(i32),
+    /// The mode with the closest refresh rate, in Hz
+    Nearest(f64),
+    /// A refresh rate within `tolerance_mhz` of `mhz`
+    Within {
+        /// Target refresh rate in mHz
+        mhz: i32,
+        /// Maximum allowed difference in mHz
+        tolerance_mhz: i32,
+    },
+}
+
+impl WlRefreshRate {
+    /// Distance of `refresh_mhz` from the requested rate, or `None` if it
+    /// does not match at all
+    fn distance(self, refresh_mhz: i32) -> Option<f64> {
+        let refresh_mhz = f64::from(refresh_mhz);
+        match self {
+            Self::Hz(hz) if (refresh_mhz / 1000.0) as i32 == hz => {
+                Some((refresh_mhz - f64::from(hz) * 1000.0).abs())
+            }
+            Self::Hz(_) => None,
+            Self::Exact(mhz) if refresh_mhz == f64::from(mhz) => Some(0.0),
+            Self::Exact(_) => None,
+            Self::Nearest(hz) => Some((refresh_mhz - hz * 1000.0).abs()),
+            Self::Within { mhz, tolerance_mhz } => {
+                let distance = (refresh_mhz - f64::from(mhz)).abs();
+                (distance <= f64::from(tolerance_mhz)).then_some(distance)
+            }
+        }
+    }
+}
+
+impl From<i32> for WlRefreshRate {
+    fn from(hz: i32) -> Self {
+        Self::Hz(hz)
+    }
+}
+
+impl std::fmt::Display for WlRefreshRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hz(hz) => write!(f, "{}Hz", hz),
+            Self::Exact(mhz) => write!(f, "{}mHz", mhz),
+            Self::Nearest(hz) => write!(f, "~{}Hz", hz),
+            Self::Within { mhz, tolerance_mhz } => {
+                write!(f, "{}±{}mHz", mhz, tolerance_mhz)
+            }
+        }
+    }
+}
+
+bitflags::bitflags! {
+    /// Which properties of a monitor changed in a
+    /// [`WlMonitorEvent::Changed`](crate::WlMonitorEvent::Changed) event
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    pub struct WlMonitorChanges: u32 {
+        /// `enabled`
+        const ENABLED = 1 << 0;
+        /// `current_mode` and `resolution`, including the refresh rate of
+        /// the current mode
+        const MODE = 1 << 1;
+        /// `position`
+        const POSITION = 1 << 2;
+        /// `scale`
+        const SCALE = 1 << 3;
+        /// `transform`
+        const TRANSFORM = 1 << 4;
+        /// `modes`: modes were added or removed, or a mode other than the
+        /// current one changed
+        const MODES_LIST = 1 << 5;
+        /// `description`
+        const DESCRIPTION = 1 << 6;
+        /// `adaptive_sync`
+        const ADAPTIVE_SYNC = 1 << 7;
+        /// `name`, `make`, `model`, `serial_number` or the physical size
+        const IDENTITY = 1 << 8;
+    }
+}
+
 /// Represents a connected monitor/display
 #[derive(Clone)]
 pub struct WlMonitor {
@@ -113,9 +238,13 @@ pub struct WlMonitor {
     pub model: String,
     /// Serial number
     pub serial_number: String,
+    /// Physical width in millimeters (0 if unknown, e.g. projectors)
+    pub physical_width_mm: i32,
+    /// Physical height in millimeters (0 if unknown, e.g. projectors)
+    pub physical_height_mm: i32,
     /// List of available display modes
     pub modes: Vec<WlMonitorMode>,
-    /// Current resolution
+    /// Resolution of the current mode
     pub resolution: WlResolution,
     /// Current position in the global coordinate space
     pub position: WlPosition,
@@ -127,10 +256,12 @@ pub struct WlMonitor {
     pub current_mode: Option<ZwlrOutputModeV1>,
     /// Current transformation (normal, rotated, flipped, etc.)
     pub transform: WlTransform,
+    /// Current adaptive sync (VRR) state
+    pub adaptive_sync: WlAdaptiveSync,
     /// Internal Wayland head proxy object
     pub head: ZwlrOutputHeadV1,
-    /// Internal flag indicating if the monitor state has changed
-    pub changed: bool,
+    /// Properties changed since the last `Changed` event (internal)
+    pub changes: WlMonitorChanges,
     /// Stores the mode ID before the monitor was disabled
     pub last_mode: Option<ObjectId>,
 }
@@ -144,14 +275,136 @@ impl std::fmt::Debug for WlMonitor {
             .field("make", &self.make)
             .field("model", &self.model)
             .field("serial_number", &self.serial_number)
+            .field("physical_width_mm", &self.physical_width_mm)
+            .field("physical_height_mm", &self.physical_height_mm)
             .field("modes", &self.modes)
             .field("resolution", &self.resolution)
             .field("position", &self.position)
             .field("scale", &self.scale)
             .field("enabled", &self.enabled)
             .field("transform", &self.transform)
-            .field("changed", &self.changed)
+            .field("adaptive_sync", &self.adaptive_sync)
+            .field("changes", &self.changes)
             .field("last_mode", &self.last_mode)
             .finish_non_exhaustive()
     }
 }
+
+impl WlMonitor {
+    /// Find the mode with the given resolution whose refresh rate best
+    /// matches `refresh`
+    pub fn find_mode(
+        &self,
+        width: i32,
+        height: i32,
+        refresh: impl Into<WlRefreshRate>,
+    ) -> Option<&WlMonitorMode> {
+        let refresh = refresh.into();
+        self.modes
+            .iter()
+            .filter(|m| {
+                m.resolution.width == width && m.resolution.height == height
+            })
+            .filter_map(|m| Some((m, refresh.distance(m.refresh_mhz)?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(m, _)| m)
+    }
+
+    /// Size of the monitor in the global coordinate space
+    ///
+    /// This is the current resolution, swapped for 90/270 degree transforms
+    /// and divided by the scale factor, rounded down as wlroots does.
+    pub fn logical_size(&self) -> WlResolution {
+        let (width, height) = match self.transform {
+            WlTransform::Rotate90
+            | WlTransform::Rotate270
+            | WlTransform::Flipped90
+            | WlTransform::Flipped270 => {
+                (self.resolution.height, self.resolution.width)
+            }
+            _ => (self.resolution.width, self.resolution.height),
+        };
+        if !self.scale.is_finite() || self.scale <= 0.0 {
+            return WlResolution { width, height };
+        }
+        WlResolution {
+            width: (f64::from(width) / self.scale) as i32,
+            height: (f64::from(height) / self.scale) as i32,
+        }
+    }
+
+    /// Pixel density of the current mode, in dots per inch
+    ///
+    /// Computed along the diagonal. Returns `None` if the monitor has no
+    /// current mode or does not report a physical size.
+    pub fn dpi(&self) -> Option<f64> {
+        if self.physical_width_mm <= 0 || self.physical_height_mm <= 0 {
+            return None;
+        }
+        let mode = self.modes.iter().find(|m| m.is_current)?;
+        let diagonal_px = f64::from(mode.resolution.width)
+            .hypot(f64::from(mode.resolution.height));
+        let diagonal_mm = f64::from(self.physical_width_mm)
+            .hypot(f64::from(self.physical_height_mm));
+        if diagonal_px <= 0.0 {
+            return None;
+        }
+        Some(diagonal_px * 25.4 / diagonal_mm)
+    }
+
+    /// Suggested scale factor for the current mode, based on [`Self::dpi`]
+    ///
+    /// Uses 96 DPI as the reference for a scale of 1.0 and rounds to the
+    /// nearest quarter, never going below 1.0.
+    pub fn suggested_scale(&self) -> Option<f64> {
+        let scale = (self.dpi()? / 96.0 * 4.0).round() / 4.0;
+        Some(scale.max(1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WlRefreshRate;
+
+    #[test]
+    fn hz_truncates_refresh_rate() {
+        assert_eq!(WlRefreshRate::Hz(60).distance(60_000), Some(0.0));
+        assert_eq!(WlRefreshRate::Hz(60).distance(60_500), Some(500.0));
+        assert_eq!(WlRefreshRate::Hz(60).distance(59_940), None);
+        assert_eq!(WlRefreshRate::Hz(59).distance(59_940), Some(940.0));
+    }
+
+    #[test]
+    fn exact_matches_only_same_rate() {
+        assert_eq!(WlRefreshRate::Exact(59_940).distance(59_940), Some(0.0));
+        assert_eq!(WlRefreshRate::Exact(59_940).distance(60_000), None);
+        assert_eq!(WlRefreshRate::Exact(59_940).distance(59_939), None);
+    }
+
+    #[test]
+    fn nearest_prefers_closest_rate() {
+        let nearest = WlRefreshRate::Nearest(59.94);
+        let ntsc = nearest.distance(59_940).unwrap();
+        let sixty = nearest.distance(60_000).unwrap();
+        assert!(ntsc < sixty);
+
+        let nearest = WlRefreshRate::Nearest(60.0);
+        assert!(
+            nearest.distance(60_000).unwrap()
+                < nearest.distance(59_940).unwrap()
+        );
+    }
+
+    #[test]
+    fn within_includes_tolerance_boundary() {
+        let within = WlRefreshRate::Within {
+            mhz: 60_000,
+            tolerance_mhz: 60,
+        };
+        assert_eq!(within.distance(60_000), Some(0.0));
+        assert_eq!(within.distance(59_940), Some(60.0));
+        assert_eq!(within.distance(60_060), Some(60.0));
+        assert_eq!(within.distance(59_939), None);
+        assert_eq!(within.distance(60_061), None);
+    }
+}